@@ -0,0 +1,23 @@
+//! Times `fuzzy_find` over a generated tree, run with `cargo bench --bench fuzzy`
+
+use filepath_tree::PathStore;
+use std::time::Instant;
+
+fn main() {
+	let mut store = PathStore::new(None::<()>);
+	for i in 0..200_000 {
+		let path = format!("/home/user{}/projects/p{}/src/module{}/file{}.rs", i % 10, i % 100, i % 1000, i);
+		store.add_path(path, None).unwrap();
+	}
+	println!("{} nodes", store.size());
+
+	let runs = 5;
+	for query in &["p42src", "user3mod7file", "file199999", "zzzz"] {
+		let start = Instant::now();
+		let mut found = 0;
+		for _ in 0..runs {
+			found = store.fuzzy_find(query, 10).len();
+		}
+		println!("{:<16} {:>3} results  {:?} per search", query, found, start.elapsed() / runs);
+	}
+}
@@ -0,0 +1,108 @@
+//! Times inserting 1M paths, run with `cargo bench --bench insert`
+//!
+//! Along with `add_path` itself, the same paths go into two bare trees that differ only in how
+//! nodes are wrapped, `Arc<RwLock<..>>` as `PathStore` does and `Rc<RefCell<..>>`, to show what
+//! the locks cost on their own
+
+use filepath_tree::PathStore;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Component, Path};
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+struct LockedNode {
+	items: BTreeMap<OsString, Arc<RwLock<LockedNode>>>,
+}
+
+struct CellNode {
+	items: BTreeMap<OsString, Rc<RefCell<CellNode>>>,
+}
+
+fn names(path: &Path) -> impl Iterator<Item = &std::ffi::OsStr> {
+	path.components().filter_map(|c| match c {
+		Component::Normal(name) => Some(name),
+		_ => None,
+	})
+}
+
+fn insert_locked(root: &Arc<RwLock<LockedNode>>, path: &Path) {
+	let mut current = root.clone();
+	for name in names(path) {
+		let existing = current.read().unwrap().items.get(name).cloned();
+		current = match existing {
+			Some(child) => child,
+			None => {
+				let child = Arc::new(RwLock::new(LockedNode { items: BTreeMap::new() }));
+				current.write().unwrap().items.insert(name.to_os_string(), child.clone());
+				child
+			}
+		};
+	}
+}
+
+fn insert_cell(root: &Rc<RefCell<CellNode>>, path: &Path) {
+	let mut current = root.clone();
+	for name in names(path) {
+		let existing = current.borrow().items.get(name).cloned();
+		current = match existing {
+			Some(child) => child,
+			None => {
+				let child = Rc::new(RefCell::new(CellNode { items: BTreeMap::new() }));
+				current.borrow_mut().items.insert(name.to_os_string(), child.clone());
+				child
+			}
+		};
+	}
+}
+
+fn time<F: FnMut()>(runs: u32, mut f: F) -> Duration {
+	let start = Instant::now();
+	for _ in 0..runs {
+		f();
+	}
+	start.elapsed() / runs
+}
+
+fn main() {
+	let paths: Vec<_> = (0..1_000_000)
+		.map(|i| format!("/home/user{}/projects/p{}/src/module{}/file{}.rs", i % 10, i % 100, i % 1000, i))
+		.collect();
+	let runs = 3;
+
+	let store = time(runs, || {
+		let mut store = PathStore::new(None::<()>);
+		for path in &paths {
+			store.add_path(path, None).unwrap();
+		}
+	});
+	// Taken in turns so neither tree always runs on a heap the other just freed
+	let (mut locked, mut cell) = (Duration::ZERO, Duration::ZERO);
+	for _ in 0..runs {
+		locked += time(1, || {
+			let root = Arc::new(RwLock::new(LockedNode { items: BTreeMap::new() }));
+			for path in &paths {
+				insert_locked(&root, Path::new(path));
+			}
+		});
+		cell += time(1, || {
+			let root = Rc::new(RefCell::new(CellNode { items: BTreeMap::new() }));
+			for path in &paths {
+				insert_cell(&root, Path::new(path));
+			}
+		});
+	}
+	let (locked, cell) = (locked / runs, cell / runs);
+
+	println!("add_path          {:?}", store);
+	println!("Arc<RwLock> tree  {:?}", locked);
+	println!("Rc<RefCell> tree  {:?}", cell);
+	let overhead = locked.as_secs_f64() - cell.as_secs_f64();
+	println!(
+		"RwLock overhead   {:.0}ns per insert, {:.1}% of add_path",
+		overhead * 1e9 / paths.len() as f64,
+		100.0 * overhead / store.as_secs_f64()
+	);
+}
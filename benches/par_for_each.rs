@@ -0,0 +1,58 @@
+//! Times `par_for_each` against `for_each` on a wide and a narrow tree, run with
+//! `cargo bench --bench par_for_each --features rayon`
+
+use filepath_tree::PathStore;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Stands in for work done on each path, so the walk itself isn't all that is timed
+fn work(path: &Path) -> usize {
+	path.as_os_str().len() % 7
+}
+
+fn time(name: &str, store: &PathStore<usize>) {
+	let runs = 5;
+	let start = Instant::now();
+	let mut total = 0;
+	for _ in 0..runs {
+		total = 0;
+		store.for_each(|path, data| total += work(path) + data.copied().unwrap_or(0));
+	}
+	let sequential = start.elapsed() / runs;
+
+	let start = Instant::now();
+	let par_total = AtomicUsize::new(0);
+	for _ in 0..runs {
+		par_total.store(0, Ordering::Relaxed);
+		store.par_for_each(|path, data| {
+			par_total.fetch_add(work(path) + data.copied().unwrap_or(0), Ordering::Relaxed);
+		});
+	}
+	let parallel = start.elapsed() / runs;
+	assert_eq!(par_total.into_inner(), total);
+	println!(
+		"{:<8} {:>8} nodes  for_each {:?}  par_for_each {:?}  {:.1}x",
+		name,
+		store.size(),
+		sequential,
+		parallel,
+		sequential.as_secs_f64() / parallel.as_secs_f64()
+	);
+}
+
+fn main() {
+	let mut wide = PathStore::new(None);
+	for i in 0..500_000 {
+		let path = format!("/home/user{}/projects/p{}/src/file{}.rs", i % 10, i % 1000, i);
+		wide.add_path(path, Some(i % 3)).unwrap();
+	}
+	time("wide", &wide);
+
+	let mut narrow = PathStore::new(None);
+	for chain in 0..64 {
+		let path = format!("/chain{}{}", chain, "/c".repeat(2000));
+		narrow.add_path(path, Some(chain)).unwrap();
+	}
+	time("narrow", &narrow);
+}
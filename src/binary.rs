@@ -0,0 +1,240 @@
+use crate::{check_name, PathNode, PathStore};
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// Written at the start of every file, the last byte is the format version
+const MAGIC: &[u8; 4] = b"FPT\x01";
+
+impl<T> PathStore<T> {
+	/// Write the tree in a compact binary format that `read_binary` can load
+	///
+	/// Each node is written once, parents before children, as a flag for whether it has data, the
+	/// data written by encode, then the number of children. Each child starts with its name as a
+	/// length and the raw bytes. Lengths and counts are LEB128 varints, so shared prefixes are only
+	/// stored once and most nodes take a few bytes plus their name. Only the paths and data are
+	/// written, not settings like the base. Names are written as raw bytes on Unix, elsewhere
+	/// names that are not valid UTF-8 are an `InvalidInput` error
+	pub fn write_binary<W, F>(&self, mut out: W, mut encode: F) -> io::Result<()>
+	where
+		W: Write,
+		F: FnMut(&T, &mut W) -> io::Result<()>,
+	{
+		out.write_all(MAGIC)?;
+		// Name (None for the root) and node still to write
+		let mut stack: Vec<(Option<OsString>, _)> = vec![(None, self.root.clone())];
+		while let Some((name, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when writing tree");
+			if let Some(name) = name {
+				let name = name_bytes(&name)?;
+				write_varint(&mut out, name.len() as u64)?;
+				out.write_all(&name)?;
+			}
+			match &node.data {
+				Some(data) => {
+					out.write_all(&[1])?;
+					encode(data, &mut out)?;
+				}
+				None => out.write_all(&[0])?,
+			}
+			write_varint(&mut out, node.items.len() as u64)?;
+
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				stack.push((Some(name.clone()), child.clone()));
+			}
+		}
+		out.flush()
+	}
+
+	/// Load a store written by `write_binary`, with decode reading back what encode wrote
+	///
+	/// Anything that doesn't match the format gives an error rather than a panic, a file cut
+	/// short is `UnexpectedEof` and the rest are `InvalidData`. The store has the default settings
+	pub fn read_binary<R, F>(mut input: R, mut decode: F) -> io::Result<PathStore<T>>
+	where
+		R: Read,
+		F: FnMut(&mut R) -> io::Result<T>,
+	{
+		let mut magic = [0; 4];
+		input.read_exact(&mut magic)?;
+		if &magic != MAGIC {
+			return Err(invalid_data("Not a path tree file"));
+		}
+
+		let (data, children) = read_node(&mut input, &mut decode)?;
+		let mut store = PathStore::new(data);
+		// Nodes along with how many of their children are still to read
+		let mut stack = vec![(store.root.clone(), children)];
+		while let Some((node, remaining)) = stack.last_mut() {
+			if *remaining == 0 {
+				stack.pop();
+				continue;
+			}
+			*remaining -= 1;
+			let node = node.clone();
+
+			let len = read_varint(&mut input)?;
+			let mut name = Vec::new();
+			(&mut input).take(len).read_to_end(&mut name)?;
+			if name.len() as u64 != len {
+				return Err(io::ErrorKind::UnexpectedEof.into());
+			}
+			let name = name_from_bytes(name)?;
+			check_name(&name).map_err(|e| invalid_data(&e.to_string()))?;
+
+			let (data, children) = read_node(&mut input, &mut decode)?;
+			let child = PathNode::with_parent(data, &node);
+			if node.write().unwrap().items.insert(name, child.clone()).is_some() {
+				return Err(invalid_data("Name appears twice under the same node"));
+			}
+			store.size += 1;
+			stack.push((child, children));
+		}
+		PathNode::recount(&store.root);
+		Ok(store)
+	}
+}
+
+/// Read the data flag, data and child count of a node
+fn read_node<R: Read, T, F: FnMut(&mut R) -> io::Result<T>>(input: &mut R, decode: &mut F) -> io::Result<(Option<T>, u64)> {
+	let mut flag = [0];
+	input.read_exact(&mut flag)?;
+	let data = match flag[0] {
+		0 => None,
+		1 => Some(decode(input)?),
+		_ => return Err(invalid_data("Bad data flag")),
+	};
+	Ok((data, read_varint(input)?))
+}
+
+fn write_varint<W: Write>(out: &mut W, mut value: u64) -> io::Result<()> {
+	loop {
+		let byte = (value & 0x7f) as u8;
+		value >>= 7;
+		if value == 0 {
+			return out.write_all(&[byte]);
+		}
+		out.write_all(&[byte | 0x80])?;
+	}
+}
+
+fn read_varint<R: Read>(input: &mut R) -> io::Result<u64> {
+	let mut value = 0;
+	for shift in (0..64).step_by(7) {
+		let mut byte = [0];
+		input.read_exact(&mut byte)?;
+		value |= u64::from(byte[0] & 0x7f) << shift;
+		if byte[0] & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(invalid_data("Varint is too long"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &OsStr) -> io::Result<Cow<'_, [u8]>> {
+	Ok(Cow::Borrowed(name.as_bytes()))
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &OsStr) -> io::Result<Cow<'_, [u8]>> {
+	match name.to_str() {
+		Some(name) => Ok(Cow::Borrowed(name.as_bytes())),
+		None => Err(io::Error::new(io::ErrorKind::InvalidInput, "Name is not valid UTF-8")),
+	}
+}
+
+#[cfg(unix)]
+fn name_from_bytes(name: Vec<u8>) -> io::Result<OsString> {
+	Ok(OsString::from_vec(name))
+}
+
+#[cfg(not(unix))]
+fn name_from_bytes(name: Vec<u8>) -> io::Result<OsString> {
+	String::from_utf8(name)
+		.map(OsString::from)
+		.map_err(|_| invalid_data("Name is not valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::PathStore;
+	use std::io::{self, ErrorKind, Read, Write};
+
+	fn encode<W: Write>(data: &i32, out: &mut W) -> io::Result<()> {
+		out.write_all(&data.to_le_bytes())
+	}
+
+	fn decode<R: Read>(input: &mut R) -> io::Result<i32> {
+		let mut bytes = [0; 4];
+		input.read_exact(&mut bytes)?;
+		Ok(i32::from_le_bytes(bytes))
+	}
+
+	#[test]
+	fn binary_round_trip() {
+		let mut store = PathStore::new(Some(-1));
+		for i in 0..10_000 {
+			let path = format!("/home/user{}/projects/p{}/src/file{}.rs", i % 10, i % 100, i);
+			assert_eq!(store.add_path(path, if i % 3 == 0 { Some(i) } else { None }), Ok(true));
+		}
+
+		let mut out = Vec::new();
+		store.write_binary(&mut out, encode).unwrap();
+		let copy = PathStore::read_binary(&out[..], decode).unwrap();
+		assert!(copy == store);
+		assert_eq!(copy.size(), store.size());
+		assert_eq!(copy.count_under("/home/user1"), store.count_under("/home/user1"));
+		assert_eq!(copy.parent_path("/home/user1/projects/p1/src"), Some("/home/user1/projects/p1".into()));
+
+		let mut lines = Vec::new();
+		store.write_paths(&mut lines).unwrap();
+		assert!(out.len() < lines.len(), "{} bytes is not smaller than {}", out.len(), lines.len());
+	}
+
+	#[test]
+	fn binary_bad_input() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2)), Ok(true));
+		let mut out = Vec::new();
+		store.write_binary(&mut out, encode).unwrap();
+
+		for len in 0..out.len() {
+			let error = PathStore::read_binary(&out[..len], decode).err().expect("Truncated file was read");
+			assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+		}
+
+		let mut bad = out.clone();
+		bad[0] = b'X';
+		assert_eq!(PathStore::read_binary(&bad[..], decode).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+		// Rename /g to /f so the root has the same name twice
+		let g = out.iter().rposition(|b| *b == b'g').unwrap();
+		let mut bad = out.clone();
+		bad[g] = b'f';
+		assert_eq!(PathStore::read_binary(&bad[..], decode).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+		bad[g] = b'/';
+		assert_eq!(PathStore::read_binary(&bad[..], decode).err().map(|e| e.kind()), Some(ErrorKind::InvalidData));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn binary_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/f/not\xffutf8"), Some(1)), Ok(true));
+		let mut out = Vec::new();
+		store.write_binary(&mut out, encode).unwrap();
+		assert!(PathStore::read_binary(&out[..], decode).unwrap() == store);
+	}
+}
@@ -0,0 +1,168 @@
+use crate::{fold_case, AllPaths, PathNode, PathStore};
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Paths that differ between two stores
+///
+/// Created with `PathStore::diff`, each list is sorted so parents come before their children
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PathDiff {
+	/// Paths only in the other store
+	pub added: Vec<PathBuf>,
+	/// Paths only in this store
+	pub removed: Vec<PathBuf>,
+	/// Paths in both stores with different data
+	pub changed: Vec<PathBuf>,
+}
+
+impl PathDiff {
+	/// Returns true if the stores have the same paths with the same data
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl<T> PathStore<T> {
+	/// Compare this store against a newer other, listing what it would take to turn this into other
+	///
+	/// Every node counts, not just the ones `walk` returns, so adding `/a/b` to a store without `/a`
+	/// adds both. Everything under an added or removed node is listed too. Both trees are walked
+	/// side by side, so the order paths were added in doesn't matter, and subtrees the two stores
+	/// share are skipped. When both stores ignore case, names are matched the way lookups match
+	/// them, so `Foo` in one and `foo` in the other is the same node and changes nothing
+	pub fn diff(&self, other: &PathStore<T>) -> PathDiff
+	where
+		T: PartialEq,
+	{
+		let case_insensitive = self.case_insensitive && other.case_insensitive;
+		let mut diff = PathDiff::default();
+		let mut stack = vec![(self.root_path(), self.root.clone(), other.root.clone())];
+
+		while let Some((path, old, new)) = stack.pop() {
+			// The same node, which also keeps it from being locked twice
+			if Arc::ptr_eq(&old, &new) {
+				continue;
+			}
+			// Children are copied out so neither store stays locked while the lists are matched up
+			let (old_items, new_items) = {
+				let old = old.read().expect("Failed to lock tree node when comparing trees");
+				let new = new.read().expect("Failed to lock tree node when comparing trees");
+				if old.data != new.data {
+					diff.changed.push(path.clone());
+				}
+				// Each child with the name it is matched by, sorted by that name
+				let copy = |node: &PathNode<T>| -> Vec<_> {
+					if !case_insensitive {
+						return node.items.iter().map(|(name, child)| (name.clone(), name.clone(), child.clone())).collect();
+					}
+					let mut items: Vec<_> =
+						node.items.iter().map(|(name, child)| (fold_case(name), name.clone(), child.clone())).collect();
+					items.sort_by(|a, b| a.0.cmp(&b.0));
+					items
+				};
+				(copy(&old), copy(&new))
+			};
+
+			// Both lists are sorted, so matching names are found by walking them together
+			let mut old_items = old_items.into_iter().peekable();
+			let mut new_items = new_items.into_iter().peekable();
+			loop {
+				let order = match (old_items.peek(), new_items.peek()) {
+					(Some((a, _, _)), Some((b, _, _))) => a.cmp(b),
+					(Some(_), None) => Ordering::Less,
+					(None, Some(_)) => Ordering::Greater,
+					(None, None) => break,
+				};
+				match order {
+					Ordering::Less => {
+						let (_, name, child) = old_items.next().expect("Peeked above");
+						diff.removed.extend(AllPaths::new(path.join(name), child));
+					}
+					Ordering::Greater => {
+						let (_, name, child) = new_items.next().expect("Peeked above");
+						diff.added.extend(AllPaths::new(path.join(name), child));
+					}
+					Ordering::Equal => {
+						let (_, name, old_child) = old_items.next().expect("Peeked above");
+						let (_, _, new_child) = new_items.next().expect("Peeked above");
+						stack.push((path.join(name), old_child, new_child));
+					}
+				}
+			}
+		}
+
+		diff.added.sort();
+		diff.removed.sort();
+		diff.changed.sort();
+		diff
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::PathStore;
+	use std::path::PathBuf;
+
+	fn paths(paths: &[&str]) -> Vec<PathBuf> {
+		paths.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn diff() {
+		let mut old = PathStore::new(None);
+		assert_eq!(old.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(old.add_path("/f/FDrive/hello", Some(2)), Ok(true));
+		assert_eq!(old.add_path("/g/x/y", None), Ok(true));
+		assert_eq!(old.add_path("/h", Some(3)), Ok(true));
+
+		// Built in a different order
+		let mut new = PathStore::new(None);
+		assert_eq!(new.add_path("/h", Some(4)), Ok(true));
+		assert_eq!(new.add_path("/f/a/b", None), Ok(true));
+		assert_eq!(new.add_path("/f/FDrive/hello", Some(2)), Ok(true));
+		assert_eq!(new.add_path("/f/FDrive", Some(5)), Ok(false));
+
+		let diff = old.diff(&new);
+		assert_eq!(diff.added, paths(&["/f/a", "/f/a/b"]));
+		assert_eq!(diff.removed, paths(&["/f/FDrive/files", "/g", "/g/x", "/g/x/y"]));
+		assert_eq!(diff.changed, paths(&["/f/FDrive", "/h"]));
+		assert!(!diff.is_empty());
+
+		let back = new.diff(&old);
+		assert_eq!(back.added, diff.removed);
+		assert_eq!(back.removed, diff.added);
+		assert_eq!(back.changed, diff.changed);
+
+		assert!(old.diff(&old).is_empty());
+		assert!(old.diff(&old.clone()).is_empty());
+
+		let mut root = PathStore::new(Some(0));
+		assert_eq!(PathStore::new(None).diff(&root).changed, paths(&["/"]));
+		root.clear();
+		assert!(root.diff(&PathStore::new(None)).is_empty());
+	}
+
+	#[test]
+	fn diff_case_insensitive() {
+		let mut old = PathStore::new_case_insensitive(None);
+		assert_eq!(old.add_path("/Foo/bar", Some(1)), Ok(true));
+		assert_eq!(old.add_path("/B", None), Ok(true));
+		let mut new = PathStore::new_case_insensitive(None);
+		assert_eq!(new.add_path("/foo/BAR", Some(2)), Ok(true));
+		assert_eq!(new.add_path("/a", None), Ok(true));
+		assert_eq!(new.add_path("/b", None), Ok(true));
+
+		let diff = old.diff(&new);
+		assert_eq!(diff.added, paths(&["/a"]));
+		assert!(diff.removed.is_empty());
+		// Paths are given with the names this store has
+		assert_eq!(diff.changed, paths(&["/Foo/bar"]));
+
+		// Only when both ignore case
+		let mut exact = PathStore::new(None);
+		assert_eq!(exact.add_path("/foo/BAR", Some(2)), Ok(true));
+		assert_eq!(old.diff(&exact).removed, paths(&["/B", "/Foo", "/Foo/bar"]));
+	}
+}
@@ -0,0 +1,124 @@
+use crate::{PathNode, PathNodeRef, PathStore};
+
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::RwLockWriteGuard;
+
+/// A path in a `PathStore`, which may or may not have data
+///
+/// Created with `PathStore::entry`. The node for the path already exists either way, the variant
+/// only says whether it has data
+pub enum Entry<'a, T> {
+	Occupied(OccupiedEntry<'a, T>),
+	Vacant(VacantEntry<'a, T>),
+}
+
+impl<'a, T> Entry<'a, T> {
+	pub(crate) fn new(node: PathNodeRef<T>) -> Self {
+		let occupied = node.read().expect("Failed to lock tree node when getting entry").data.is_some();
+		if occupied {
+			Entry::Occupied(OccupiedEntry::new(node))
+		} else {
+			Entry::Vacant(VacantEntry {
+				node,
+				_store: PhantomData,
+			})
+		}
+	}
+
+	/// Sets the data to default if there is none, then returns access to it
+	pub fn or_insert(&mut self, default: T) -> DataMut<'_, T> {
+		self.or_insert_with(|| default)
+	}
+
+	/// Sets the data to the result of f if there is none, then returns access to it
+	///
+	/// f is not called if there is already data. The entry is occupied afterwards
+	pub fn or_insert_with<F: FnOnce() -> T>(&mut self, f: F) -> DataMut<'_, T> {
+		if let Entry::Vacant(entry) = self {
+			entry.node.write().unwrap().data = Some(f());
+			*self = Entry::Occupied(OccupiedEntry::new(entry.node.clone()));
+		}
+		match self {
+			Entry::Occupied(entry) => entry.get_mut(),
+			Entry::Vacant(_) => unreachable!("Vacant entries are filled above"),
+		}
+	}
+
+	/// Calls f on the data if there is any
+	pub fn and_modify<F: FnOnce(&mut T)>(&mut self, f: F) -> &mut Self {
+		if let Entry::Occupied(entry) = self {
+			f(&mut entry.get_mut());
+		}
+		self
+	}
+}
+
+/// An entry for a path that has data
+pub struct OccupiedEntry<'a, T> {
+	node: PathNodeRef<T>,
+	// Keeps the store borrowed so the node can't be locked elsewhere
+	_store: PhantomData<&'a mut PathStore<T>>,
+}
+
+impl<'a, T> OccupiedEntry<'a, T> {
+	pub(crate) fn new(node: PathNodeRef<T>) -> Self {
+		Self {
+			node,
+			_store: PhantomData,
+		}
+	}
+
+	/// Returns access to the data, the node stays locked until it is dropped
+	pub fn get_mut(&mut self) -> DataMut<'_, T> {
+		DataMut {
+			guard: self.node.write().unwrap(),
+		}
+	}
+
+	/// Replaces the data, returning the old data
+	pub fn insert(&mut self, data: T) -> T {
+		self.get_mut().guard.data.replace(data).expect("Entry data is always set")
+	}
+
+	/// Takes the data out, leaving the node in the store without any
+	pub fn take(self) -> T {
+		self.node.write().unwrap().data.take().expect("Entry data is always set")
+	}
+}
+
+/// An entry for a path that has no data
+pub struct VacantEntry<'a, T> {
+	node: PathNodeRef<T>,
+	// Keeps the store borrowed so the node can't be locked elsewhere
+	_store: PhantomData<&'a mut PathStore<T>>,
+}
+
+impl<'a, T> VacantEntry<'a, T> {
+	/// Sets the data, giving back an entry to get at it with
+	pub fn insert(self, data: T) -> OccupiedEntry<'a, T> {
+		self.node.write().unwrap().data = Some(data);
+		OccupiedEntry::new(self.node)
+	}
+}
+
+/// Mutable access to the data of an `Entry`
+///
+/// The node stays locked until this is dropped
+pub struct DataMut<'a, T> {
+	guard: RwLockWriteGuard<'a, PathNode<T>>,
+}
+
+impl<'a, T> Deref for DataMut<'a, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		self.guard.data.as_ref().expect("Entry data is always set")
+	}
+}
+
+impl<'a, T> DerefMut for DataMut<'a, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		self.guard.data.as_mut().expect("Entry data is always set")
+	}
+}
@@ -1,21 +1,94 @@
-use std::error::Error;
-use std::fmt;
-
-#[derive(Debug, Eq, PartialEq)]
-pub enum StorageError {
-    PathNotRelative,
-}
-
-impl fmt::Display for StorageError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            StorageError::PathNotRelative => write!(f, "Input path to store is not relative"),
-        }
-    }
-}
-
-impl Error for StorageError {
-    fn description(&self) -> &str {
-        "Some error happened when using PathStorage"
-    }
-}
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors from `PathStore`, each with the path or name it was caused by
+///
+/// Errors from turning an input path into a path in the store hold the path as it was passed in,
+/// the others hold the path after `.` and `..` were resolved
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum StorageError {
+    /// A relative path was given to a store without a base
+    PathNotAbsolute { path: PathBuf },
+    /// A rooted path was given to a relative store
+    PathNotRelative { path: PathBuf },
+    /// The path needs to be in the store but isn't
+    PathNotFound { path: PathBuf },
+    /// A relative path has more `..` than the base of the store has components
+    PathEscapesBase { path: PathBuf },
+    /// A path has more `..` than can be resolved without going above the root
+    PathEscapesRoot { path: PathBuf },
+    /// The path needs to not be in the store but is
+    TargetExists { path: PathBuf },
+    /// A name can not be stored as a single node
+    InvalidComponent { component: OsString, reason: InvalidComponentReason },
+    /// Moving a node under itself would make it its own ancestor
+    MoveIntoDescendant { from: PathBuf, to: PathBuf },
+    /// A path in a path list was rejected, line counts from 1
+    Line { line: usize, error: Box<StorageError> },
+    /// Reading a path list failed
+    Io { kind: io::ErrorKind, message: String },
+}
+
+/// Why a name was rejected with `StorageError::InvalidComponent`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InvalidComponentReason {
+    Empty,
+    ContainsNul,
+    ContainsSeparator,
+    /// `.` or `..`, which would be resolved rather than stored
+    Reserved,
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::PathNotAbsolute { path } => write!(f, "Input path {} to store is not absolute", path.display()),
+            StorageError::PathNotRelative { path } => write!(f, "Input path {} to relative store is not relative", path.display()),
+            StorageError::PathNotFound { path } => write!(f, "Input path {} is not in the store", path.display()),
+            StorageError::PathEscapesBase { path } => write!(f, "Input path {} goes above the base path of the store", path.display()),
+            StorageError::PathEscapesRoot { path } => write!(f, "Input path {} goes above the root of the store", path.display()),
+            StorageError::TargetExists { path } => write!(f, "Target path {} is already in the store", path.display()),
+            StorageError::InvalidComponent { component, reason } => {
+                write!(f, "Name {:?} can not be stored: {}", component, reason)
+            }
+            StorageError::MoveIntoDescendant { from, to } => {
+                write!(f, "Path {} can not be moved under itself to {}", from.display(), to.display())
+            }
+            StorageError::Line { line, error } => write!(f, "Line {}: {}", line, error),
+            StorageError::Io { message, .. } => write!(f, "Failed to read paths: {}", message),
+        }
+    }
+}
+
+impl fmt::Display for InvalidComponentReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidComponentReason::Empty => write!(f, "name is empty"),
+            InvalidComponentReason::ContainsNul => write!(f, "name contains a NUL byte"),
+            InvalidComponentReason::ContainsSeparator => write!(f, "name contains a path separator"),
+            InvalidComponentReason::Reserved => write!(f, "name is . or .."),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StorageError::Line { error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(error: io::Error) -> Self {
+        StorageError::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,455 @@
+use crate::{PathStore, StorageError};
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Write};
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+impl<T> PathStore<T> {
+	/// Write every path from `walk` to out, each followed by a newline
+	///
+	/// See `write_paths_with_separator` for how paths are encoded
+	pub fn write_paths<W: Write>(&self, out: &mut W) -> io::Result<()> {
+		self.write_paths_with_separator(out, b'\n')
+	}
+
+	/// Write every path from `walk` to out, each followed by separator
+	///
+	/// Paths are streamed as they are walked rather than collected first. Use `b'\0'` as the
+	/// separator for output that works with `xargs -0`. On Unix the raw bytes of each path are
+	/// written so nothing is lost, elsewhere paths are written as UTF-8 with anything invalid
+	/// replaced by U+FFFD
+	pub fn write_paths_with_separator<W: Write>(&self, out: &mut W, separator: u8) -> io::Result<()> {
+		for path in self.iter() {
+			out.write_all(&path_bytes(&path))?;
+			out.write_all(&[separator])?;
+		}
+		Ok(())
+	}
+
+	/// Write every path from `walk` to out, each followed by a newline, in the format `from_lines` reads
+	///
+	/// Unlike `write_paths`, a path that would not be read back the same is an `InvalidInput`
+	/// error before anything of it is written: a name containing a newline, a path ending in
+	/// `\r`, and elsewhere than Unix a name that is not valid UTF-8. `from_lines` gives the same
+	/// paths in a default store, use `add_lines` to read them into a store with other settings,
+	/// like a relative one. Data is not written
+	pub fn write_lines<W: Write>(&self, mut out: W) -> io::Result<()> {
+		for path in self.iter() {
+			let bytes = match exact_path_bytes(&path) {
+				Some(bytes) if !bytes.contains(&b'\n') && bytes.last() != Some(&b'\r') => bytes,
+				_ => {
+					let message = format!("Path {:?} can not be written as a line that reads back the same", path);
+					return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+				}
+			};
+			out.write_all(&bytes)?;
+			out.write_all(b"\n")?;
+		}
+		Ok(())
+	}
+
+	/// Build a store from a list of paths, one per line like the output of `find`
+	///
+	/// The store has the default settings, so every path has to be absolute. See `add_lines` for
+	/// how lines are read
+	pub fn from_lines<R, F>(input: R, data_for: F) -> Result<PathStore<T>, StorageError>
+	where
+		R: BufRead,
+		F: FnMut(&Path) -> Option<T>,
+	{
+		let mut store = PathStore::new(None);
+		store.add_lines(input, data_for)?;
+		Ok(store)
+	}
+
+	/// Same as `from_lines` for paths ending in NUL, like the output of `find -print0`
+	///
+	/// `write_paths_with_separator` with `b'\0'` writes this format
+	pub fn from_nul_delimited<R, F>(input: R, data_for: F) -> Result<PathStore<T>, StorageError>
+	where
+		R: BufRead,
+		F: FnMut(&Path) -> Option<T>,
+	{
+		let mut store = PathStore::new(None);
+		store.add_nul_delimited(input, data_for)?;
+		Ok(store)
+	}
+
+	/// Add every path from a list of paths, one per line, with the settings of this store
+	///
+	/// data_for gives the data to store at each path. Empty lines are skipped and a `\r` before
+	/// the newline is dropped. A path that can't be added is a `Line` error holding the line number
+	/// and why, reading stops there and the paths before it stay added. On Unix each line is used
+	/// as raw bytes, elsewhere lines have to be UTF-8
+	pub fn add_lines<R, F>(&mut self, input: R, data_for: F) -> Result<(), StorageError>
+	where
+		R: BufRead,
+		F: FnMut(&Path) -> Option<T>,
+	{
+		self.add_delimited(input, b'\n', data_for)
+	}
+
+	/// Same as `add_lines` for paths ending in NUL
+	pub fn add_nul_delimited<R, F>(&mut self, input: R, data_for: F) -> Result<(), StorageError>
+	where
+		R: BufRead,
+		F: FnMut(&Path) -> Option<T>,
+	{
+		self.add_delimited(input, b'\0', data_for)
+	}
+
+	fn add_delimited<R, F>(&mut self, input: R, delimiter: u8, mut data_for: F) -> Result<(), StorageError>
+	where
+		R: BufRead,
+		F: FnMut(&Path) -> Option<T>,
+	{
+		for (i, line) in input.split(delimiter).enumerate() {
+			let mut line = line?;
+			if delimiter == b'\n' && line.last() == Some(&b'\r') {
+				line.pop();
+			}
+			if line.is_empty() {
+				continue;
+			}
+			let added = path_from_bytes(line).and_then(|path| {
+				let data = data_for(&path);
+				self.add_path(&path, data)
+			});
+			added.map_err(|error| StorageError::Line {
+				line: i + 1,
+				error: Box::new(error),
+			})?;
+		}
+		Ok(())
+	}
+
+	/// Render the tree as a Graphviz digraph, each node labelled with its name
+	///
+	/// Every node gets its own id, so different paths with the same name stay separate.
+	/// The output can be piped to `dot -Tpng`
+	pub fn to_dot(&self) -> String {
+		self.dot_with(|name, _| escape_dot(&name.to_string_lossy()))
+	}
+
+	/// Same as `to_dot`, with the data of each node that has any added below its name
+	pub fn to_dot_with_data(&self) -> String
+	where
+		T: Display,
+	{
+		self.dot_with(|name, data| {
+			let name = escape_dot(&name.to_string_lossy());
+			match data {
+				Some(data) => format!("{}\\n{}", name, escape_dot(&data.to_string())),
+				None => name,
+			}
+		})
+	}
+
+	/// Write the tree like the `tree` command does, one node per line under its parent
+	///
+	/// Only names are shown, the `Display` impl also shows data
+	pub fn fmt_tree(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		self.fmt_tree_with(f, |f, name, _| write!(f, "{}", name.to_string_lossy()))
+	}
+
+	fn fmt_tree_with<F>(&self, f: &mut Formatter<'_>, label: F) -> fmt::Result
+	where
+		F: Fn(&mut Formatter<'_>, &OsStr, Option<&T>) -> fmt::Result,
+	{
+		// Indent to write before the node, whether it is the last child (None for the root), name and node
+		let mut stack = vec![(String::new(), None, self.root_label(), self.root.clone())];
+
+		while let Some((indent, last, name, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when printing tree");
+			let (connector, child_indent) = match last {
+				Some(false) => ("├── ", format!("{}│   ", indent)),
+				Some(true) => ("└── ", format!("{}    ", indent)),
+				None => ("", indent.clone()),
+			};
+			write!(f, "{}{}", indent, connector)?;
+			label(f, &name, node.data.as_ref())?;
+			writeln!(f)?;
+
+			// Reversed so children come off the stack in sorted order
+			for (i, (name, child)) in node.items.iter().rev().enumerate() {
+				stack.push((child_indent.clone(), Some(i == 0), name.clone(), child.clone()));
+			}
+		}
+		Ok(())
+	}
+
+	fn dot_with<F: Fn(&OsStr, Option<&T>) -> String>(&self, label: F) -> String {
+		let mut out = String::from("digraph {\n");
+		let mut next_id = 0;
+		// Parent id, name and node still to write
+		let mut stack = vec![(None, self.root_label(), self.root.clone())];
+
+		while let Some((parent, name, node)) = stack.pop() {
+			let id = next_id;
+			next_id += 1;
+
+			let node = node.read().expect("Failed to lock tree node when exporting tree");
+			out.push_str(&format!("\tn{} [label=\"{}\"];\n", id, label(&name, node.data.as_ref())));
+			if let Some(parent) = parent {
+				out.push_str(&format!("\tn{} -> n{};\n", parent, id));
+			}
+			for (name, child) in node.items.iter().rev() {
+				stack.push((Some(id), name.clone(), child.clone()));
+			}
+		}
+
+		out.push_str("}\n");
+		out
+	}
+
+	/// Name shown for the root in DOT and tree output, `/` or `.` in a relative store where the
+	/// root is the empty path
+	pub(crate) fn root_label(&self) -> OsString {
+		if self.relative {
+			OsString::from(".")
+		} else {
+			self.root_path().into_os_string()
+		}
+	}
+}
+
+impl<T: Display> Display for PathStore<T> {
+	/// Same as `fmt_tree`, with data shown in brackets after the name
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		self.fmt_tree_with(f, |f, name, data| {
+			write!(f, "{}", name.to_string_lossy())?;
+			match data {
+				Some(data) => write!(f, " ({})", data),
+				None => Ok(()),
+			}
+		})
+	}
+}
+
+/// Escape a string for use inside a quoted DOT label
+fn escape_dot(s: &str) -> String {
+	let mut out = String::with_capacity(s.len());
+	for c in s.chars() {
+		match c {
+			'"' | '\\' => {
+				out.push('\\');
+				out.push(c);
+			}
+			'\n' => out.push_str("\\n"),
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+	Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+	match path.to_string_lossy() {
+		Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+		Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+	}
+}
+
+/// Bytes of path that read back as the same path
+#[cfg(unix)]
+fn exact_path_bytes(path: &Path) -> Option<Cow<'_, [u8]>> {
+	Some(path_bytes(path))
+}
+
+#[cfg(not(unix))]
+fn exact_path_bytes(path: &Path) -> Option<Cow<'_, [u8]>> {
+	path.to_str().map(|path| Cow::Borrowed(path.as_bytes()))
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, StorageError> {
+	Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf, StorageError> {
+	String::from_utf8(bytes).map(PathBuf::from).map_err(|_| StorageError::Io {
+		kind: io::ErrorKind::InvalidData,
+		message: String::from("Path is not valid UTF-8"),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{PathStore, StorageError};
+	use std::path::PathBuf;
+
+	#[test]
+	fn write_paths() {
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut out = Vec::new();
+		store.write_paths(&mut out).unwrap();
+		assert_eq!(out, b"/f/FDrive/files\n/f/FDrive/hello\n/g\n");
+
+		let mut out = Vec::new();
+		store.write_paths_with_separator(&mut out, b'\0').unwrap();
+		assert_eq!(out, b"/f/FDrive/files\0/f/FDrive/hello\0/g\0");
+	}
+
+	#[test]
+	fn to_dot() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g/files", None), Ok(true));
+		assert_eq!(store.add_path("/g/\"quoted\\", None), Ok(true));
+		let size = store.size();
+
+		assert_eq!(store.to_dot(), concat!(
+			"digraph {\n",
+			"\tn0 [label=\"/\"];\n",
+			"\tn1 [label=\"f\"];\n",
+			"\tn0 -> n1;\n",
+			"\tn2 [label=\"files\"];\n",
+			"\tn1 -> n2;\n",
+			"\tn3 [label=\"g\"];\n",
+			"\tn0 -> n3;\n",
+			"\tn4 [label=\"\\\"quoted\\\\\"];\n",
+			"\tn3 -> n4;\n",
+			"\tn5 [label=\"files\"];\n",
+			"\tn3 -> n5;\n",
+			"}\n",
+		));
+		assert!(store.to_dot_with_data().contains("\tn2 [label=\"files\\n1\"];\n"));
+		assert_eq!(store.size(), size);
+
+		let mut store = PathStore::new_relative(Some(0));
+		assert_eq!(store.add_path("a", Some(1)), Ok(true));
+		assert_eq!(store.to_dot(), "digraph {\n\tn0 [label=\".\"];\n\tn1 [label=\"a\"];\n\tn0 -> n1;\n}\n");
+		assert!(store.to_dot_with_data().starts_with("digraph {\n\tn0 [label=\".\\n0\"];\n"));
+	}
+
+	#[test]
+	fn display_tree() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/g", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/a", None), Ok(true));
+
+		assert_eq!(store.to_string(), concat!(
+			"/\n",
+			"├── f\n",
+			"│   ├── FDrive\n",
+			"│   │   ├── files (2)\n",
+			"│   │   └── hello\n",
+			"│   └── a\n",
+			"└── g (1)\n",
+		));
+
+		struct Names<'a>(&'a PathStore<i32>);
+		impl<'a> std::fmt::Display for Names<'a> {
+			fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				self.0.fmt_tree(f)
+			}
+		}
+		assert_eq!(Names(&store).to_string().lines().last(), Some("└── g"));
+
+		let mut store = PathStore::new_relative(None);
+		assert_eq!(store.add_path("a/b", Some(1)), Ok(true));
+		assert_eq!(store.to_string(), ".\n└── a\n    └── b (1)\n");
+	}
+
+	#[test]
+	fn from_lines() {
+		let input = "/f/FDrive/files\n\n/f/FDrive/hello\r\n/g\n";
+		let store = PathStore::from_lines(input.as_bytes(), |path| Some(path.as_os_str().len())).unwrap();
+		assert_eq!(store.walk(), vec![
+			PathBuf::from("/f/FDrive/files"),
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/g"),
+		]);
+		assert_eq!(store.get_cloned("/g"), Some(2));
+
+		let mut out = Vec::new();
+		store.write_lines(&mut out).unwrap();
+		let copy = PathStore::from_lines(&out[..], |_| None::<()>).unwrap();
+		assert_eq!(copy.walk(), store.walk());
+
+		let error = PathStore::from_lines("/a\n\nrelative\n/b\n".as_bytes(), |_| None::<()>).err();
+		assert_eq!(error, Some(StorageError::Line {
+			line: 3,
+			error: Box::new(StorageError::PathNotAbsolute { path: PathBuf::from("relative") }),
+		}));
+		assert_eq!(error.unwrap().to_string(), "Line 3: Input path relative to store is not absolute");
+
+		let store = PathStore::from_nul_delimited("/a\nb\0/c\0\0".as_bytes(), |_| None::<()>).unwrap();
+		assert_eq!(store.walk(), vec![PathBuf::from("/a\nb"), PathBuf::from("/c")]);
+	}
+
+	#[test]
+	fn write_lines_edge_cases() {
+		let mut store = PathStore::new_relative(None::<()>);
+		assert_eq!(store.add_path("src/lib.rs", None), Ok(true));
+		assert_eq!(store.add_path("Cargo.toml", None), Ok(true));
+		let mut out = Vec::new();
+		store.write_lines(&mut out).unwrap();
+		assert_eq!(out, b"Cargo.toml\nsrc/lib.rs\n");
+		assert!(PathStore::from_lines(&out[..], |_| None::<()>).is_err());
+		let mut copy = PathStore::new_relative(None);
+		copy.add_lines(&out[..], |_| None::<()>).unwrap();
+		assert_eq!(copy.walk(), store.walk());
+
+		for name in &["/a/new\nline", "/a/rest\r"] {
+			let mut store = PathStore::new(None::<()>);
+			assert_eq!(store.add_path("/a/fine", None), Ok(true));
+			assert_eq!(store.add_path(name, None), Ok(true));
+			let mut out = Vec::new();
+			let error = store.write_lines(&mut out).unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+			// Paths before it are written, the rejected one is not
+			assert_eq!(out, b"/a/fine\n");
+		}
+		// Names ending in \r are fine as long as the path doesn't
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path("/a\r/b", None), Ok(true));
+		let mut out = Vec::new();
+		store.write_lines(&mut out).unwrap();
+		assert_eq!(PathStore::from_lines(&out[..], |_| None::<()>).unwrap().walk(), store.walk());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn write_paths_round_trip() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/f/not\xffutf8"), None), Ok(true));
+		assert_eq!(store.add_path("/f/new\nline", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut out = Vec::new();
+		store.write_paths_with_separator(&mut out, b'\0').unwrap();
+
+		let mut copy = PathStore::new(None::<()>);
+		for line in out.split(|b| *b == b'\0').filter(|l| !l.is_empty()) {
+			assert_eq!(copy.add_path(OsStr::from_bytes(line), None), Ok(true));
+		}
+		assert_eq!(copy.walk(), store.walk());
+		let copy = PathStore::from_nul_delimited(&out[..], |_| None::<()>).unwrap();
+		assert_eq!(copy.walk(), store.walk());
+
+		assert_eq!(store.remove_path("/f/new\nline"), Ok(None));
+		let mut out = Vec::new();
+		store.write_lines(&mut out).unwrap();
+		assert_eq!(PathStore::from_lines(&out[..], |_| None::<()>).unwrap().walk(), store.walk());
+	}
+}
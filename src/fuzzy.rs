@@ -0,0 +1,157 @@
+use crate::PathStore;
+
+use std::cmp::Reverse;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Points for each query character matched
+const MATCH_SCORE: u32 = 1;
+/// Extra points when the previous character of the path also matched
+const CONTIGUOUS_BONUS: u32 = 6;
+/// Extra points for matching the start of a name or of a word inside it
+const BOUNDARY_BONUS: u32 = 4;
+
+/// How much of the query the path down to a node has matched
+#[derive(Clone, Copy)]
+struct Progress {
+	matched: usize,
+	score: u32,
+	/// Whether the last character of the path matched
+	contiguous: bool,
+}
+
+impl Progress {
+	/// Match as much of the rest of query as possible against name, going left to right
+	fn advance(mut self, query: &[char], name: &str) -> Self {
+		let mut prev = None;
+		// The separator before a name breaks any run
+		self.contiguous = false;
+		for c in name.chars() {
+			let lower = c.to_lowercase().next().unwrap_or(c);
+			if query.get(self.matched) == Some(&lower) {
+				let boundary = match prev {
+					None => true,
+					Some(prev) => matches!(prev, '-' | '_' | '.' | ' ') || (prev.is_lowercase() && c.is_uppercase()),
+				};
+				self.score += MATCH_SCORE;
+				if self.contiguous {
+					self.score += CONTIGUOUS_BONUS;
+				}
+				if boundary {
+					self.score += BOUNDARY_BONUS;
+				}
+				self.matched += 1;
+				self.contiguous = true;
+			} else {
+				self.contiguous = false;
+			}
+			prev = Some(c);
+		}
+		self
+	}
+}
+
+impl<T> PathStore<T> {
+	/// Returns up to limit of the paths that best match query, with their scores, best first
+	///
+	/// A path matches if every character of query appears in it, in order, ignoring case, like
+	/// `fdrhel` matching `/f/FDrive/hello`. Runs of matching characters and matches at the start
+	/// of a name or word score higher. `/` in query is ignored. Every node other than the root is a
+	/// candidate, paths are only built for nodes that match. Names that aren't valid UTF-8 never
+	/// match but the nodes under them are still searched. Equal scores come in walk order
+	pub fn fuzzy_find(&self, query: &str, limit: usize) -> Vec<(PathBuf, u32)> {
+		let query: Vec<char> = query.chars().filter(|c| *c != '/').flat_map(char::to_lowercase).collect();
+		let mut out: Vec<(PathBuf, u32)> = Vec::new();
+		if limit == 0 {
+			return out;
+		}
+
+		let start = Progress { matched: 0, score: 0, contiguous: false };
+		// Depth, name (None for the root), node and progress before the node's name
+		let mut stack: Vec<(usize, Option<OsString>, _, Progress)> = vec![(0, None, self.root.clone(), start)];
+		let mut path = self.root_path();
+		let mut path_depth = 0;
+
+		while let Some((depth, name, node, progress)) = stack.pop() {
+			let progress = match name {
+				Some(name) => {
+					while path_depth >= depth {
+						path.pop();
+						path_depth -= 1;
+					}
+					path.push(&name);
+					path_depth = depth;
+
+					match name.to_str() {
+						Some(name) => {
+							let progress = progress.advance(&query, name);
+							if progress.matched == query.len() {
+								out.push((path.clone(), progress.score));
+								// Keep the best limit so far, sorting only now and then
+								if out.len() >= limit.saturating_mul(2) {
+									out.sort_by_key(|(_, score)| Reverse(*score));
+									out.truncate(limit);
+								}
+							}
+							progress
+						}
+						None => progress,
+					}
+				}
+				None => progress,
+			};
+
+			let node = node.read().expect("Failed to lock tree node when searching tree");
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				stack.push((depth + 1, Some(name.clone()), child.clone(), progress));
+			}
+		}
+
+		// Stable, so equal scores stay in walk order
+		out.sort_by_key(|(_, score)| Reverse(*score));
+		out.truncate(limit);
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::PathStore;
+	use std::path::PathBuf;
+
+	#[test]
+	fn fuzzy_find() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &["/f/FDrive/hello", "/f/FDrive/files", "/f/fdr/h/e/l", "/g/help"] {
+			assert_eq!(store.add_path(path, None), Ok(true));
+		}
+
+		let found = store.fuzzy_find("fdrhel", 10);
+		assert_eq!(
+			found.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(),
+			vec![PathBuf::from("/f/FDrive/hello"), PathBuf::from("/f/fdr/h/e/l")]
+		);
+		// "hel" is one run in hello but split up in h/e/l
+		assert!(found[0].1 > found[1].1);
+
+		assert_eq!(store.fuzzy_find("FDR/HELLO", 10)[0].0, PathBuf::from("/f/FDrive/hello"));
+		assert_eq!(store.fuzzy_find("hel", 1), vec![(PathBuf::from("/f/FDrive/hello"), 1 + 4 + 2 * (1 + 6))]);
+		assert!(store.fuzzy_find("xyz", 10).is_empty());
+		assert!(store.fuzzy_find("f", 0).is_empty());
+		assert_eq!(store.fuzzy_find("", 100).len(), store.size());
+		assert_eq!(store.fuzzy_find("fdrhel", usize::MAX), found);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn fuzzy_find_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/a\xff/abc"), None), Ok(true));
+		let found: Vec<_> = store.fuzzy_find("abc", 10).into_iter().map(|(path, _)| path).collect();
+		assert_eq!(found, vec![PathBuf::from(OsStr::from_bytes(b"/a\xff/abc"))]);
+	}
+}
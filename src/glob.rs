@@ -0,0 +1,434 @@
+use crate::{PathNodeRef, PathStore};
+
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// One `/` separated part of a glob pattern
+pub(crate) enum Segment {
+	/// `**`, any number of components including none
+	AnyDepth,
+	/// No wildcards, so the child can be looked up directly
+	Literal(String),
+	/// Contains wildcards, matched against every child
+	Wildcard(Vec<Token>),
+}
+
+impl Segment {
+	fn parse(segment: &str) -> Self {
+		if segment == "**" {
+			Segment::AnyDepth
+		} else if segment.contains(['*', '?', '[']) {
+			Segment::Wildcard(Token::parse(segment))
+		} else {
+			Segment::Literal(segment.to_owned())
+		}
+	}
+
+	/// Split a pattern on `/` into segments, empty segments are skipped
+	pub(crate) fn parse_all(pattern: &str) -> Vec<Self> {
+		pattern.split('/').filter(|s| !s.is_empty()).map(Segment::parse).collect()
+	}
+
+	/// Drop `.` segments and resolve `..` against the literal segment before it
+	///
+	/// A `..` after a wildcard is left alone, so it matches nothing. None if `..` would go above the root
+	fn normalize(segments: Vec<Self>) -> Option<Vec<Self>> {
+		let mut out: Vec<Self> = Vec::with_capacity(segments.len());
+		let mut literal_prefix = true;
+		for segment in segments {
+			match &segment {
+				Segment::Literal(name) if name == "." => continue,
+				Segment::Literal(name) if name == ".." && literal_prefix => {
+					out.pop()?;
+					continue;
+				}
+				Segment::Literal(_) => {}
+				_ => literal_prefix = false,
+			}
+			out.push(segment);
+		}
+		Some(out)
+	}
+
+	/// Whether this segment matches a single name, `**` matches any name
+	fn matches(&self, name: &OsStr) -> bool {
+		match self {
+			Segment::AnyDepth => true,
+			Segment::Literal(literal) => name == OsStr::new(literal),
+			Segment::Wildcard(pattern) => name.to_str().is_some_and(|name| wildcard_match(pattern, name)),
+		}
+	}
+}
+
+/// Whether the whole of names matches segments, with `**` standing for any number of names
+pub(crate) fn segments_match(segments: &[Segment], names: &[&OsStr]) -> bool {
+	// reached[j] is whether the segments so far can match the first j names
+	let mut reached = vec![false; names.len() + 1];
+	reached[0] = true;
+	for segment in segments {
+		let mut next = vec![false; names.len() + 1];
+		match segment {
+			Segment::AnyDepth => {
+				let mut any = false;
+				for (next, reached) in next.iter_mut().zip(reached.iter()) {
+					any |= *reached;
+					*next = any;
+				}
+			}
+			_ => {
+				for (j, name) in names.iter().enumerate() {
+					next[j + 1] = reached[j] && segment.matches(name);
+				}
+			}
+		}
+		reached = next;
+	}
+	reached[names.len()]
+}
+
+/// One part of a segment with wildcards
+pub(crate) enum Token {
+	Char(char),
+	/// `?`, any single character
+	Any,
+	/// `*`, any run of characters
+	Star,
+	/// `[...]`, any character in the ranges, or any not in them if negated
+	Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl Token {
+	fn parse(segment: &str) -> Vec<Self> {
+		let chars: Vec<char> = segment.chars().collect();
+		let mut tokens = Vec::new();
+		let mut i = 0;
+		while i < chars.len() {
+			match chars[i] {
+				'*' => tokens.push(Token::Star),
+				'?' => tokens.push(Token::Any),
+				'[' => match Self::parse_class(&chars[i + 1..]) {
+					Some((class, len)) => {
+						tokens.push(class);
+						i += len;
+					}
+					// No closing `]`, so the `[` is just a character
+					None => tokens.push(Token::Char('[')),
+				},
+				c => tokens.push(Token::Char(c)),
+			}
+			i += 1;
+		}
+		tokens
+	}
+
+	/// Parse the inside of a class, chars starts just after the `[`
+	///
+	/// Returns the class and how many chars it used including the closing `]`
+	fn parse_class(chars: &[char]) -> Option<(Self, usize)> {
+		let mut i = 0;
+		let negated = matches!(chars.first(), Some('!') | Some('^'));
+		if negated {
+			i += 1;
+		}
+		let mut ranges = Vec::new();
+		// A `]` straight after the opening is part of the class rather than closing it
+		let start = i;
+		loop {
+			let c = *chars.get(i)?;
+			if c == ']' && i > start {
+				return Some((Token::Class { negated, ranges }, i + 1));
+			}
+			match (chars.get(i + 1), chars.get(i + 2)) {
+				(Some('-'), Some(&end)) if end != ']' => {
+					ranges.push((c, end));
+					i += 3;
+				}
+				_ => {
+					ranges.push((c, c));
+					i += 1;
+				}
+			}
+		}
+	}
+
+	/// Whether this token matches c, always false for `*` which is handled by the matcher
+	fn matches(&self, c: char) -> bool {
+		match self {
+			Token::Char(expected) => *expected == c,
+			Token::Any => true,
+			Token::Star => false,
+			Token::Class { negated, ranges } => {
+				ranges.iter().any(|(start, end)| (*start..=*end).contains(&c)) != *negated
+			}
+		}
+	}
+}
+
+impl<T> PathStore<T> {
+	/// Returns the path of every node matching pattern, in the same order as `walk_all`
+	///
+	/// Patterns are absolute, or relative in a store made with `new_relative`, and split on `/`. In
+	/// a segment `*` matches any run of characters within a single component, `?` any single
+	/// character and `[...]` any character in the class, like `[abc]`, `[a-z]` or `[!0-9]` for
+	/// anything but a digit. A segment of just `**` matches any number of components, including
+	/// none. Nodes match whether or not they have data. Only the branches that can match are
+	/// visited, so literal segments like the `f` in `/f/*` go straight to that child. Components
+	/// that are not valid UTF-8 never match a wildcard
+	///
+	/// `.` segments are dropped and `..` is resolved like in other paths as long as only literal
+	/// segments come before it, so `/f/../g/*` is `/g/*`. A `..` after a wildcard or `**` can't be
+	/// resolved before matching and matches nothing, and a `..` going above the root matches nothing
+	pub fn glob<P: AsRef<str>>(&self, pattern: P) -> Vec<PathBuf> {
+		let pattern = pattern.as_ref();
+		if pattern.starts_with('/') == self.relative {
+			return Vec::new();
+		}
+		let segments = match Segment::normalize(Segment::parse_all(pattern)) {
+			Some(segments) => segments,
+			None => return Vec::new(),
+		};
+
+		let mut out = BTreeSet::new();
+		// `**` can reach the same node at the same point in the pattern more than one way. Nodes
+		// are told apart by address, which is stable while the store is borrowed
+		let mut seen = HashSet::new();
+		let mut stack = vec![(self.root_path(), self.root.clone(), 0)];
+
+		while let Some((path, node, index)) = stack.pop() {
+			if !seen.insert((Arc::as_ptr(&node), index)) {
+				continue;
+			}
+			if index == segments.len() {
+				out.insert(path);
+				continue;
+			}
+
+			let node_ref = node.clone();
+			let node = node.read().expect("Failed to lock tree node when matching glob");
+			let mut push_child = |name: &OsStr, child: &PathNodeRef<T>, index| {
+				stack.push((path.join(name), child.clone(), index));
+			};
+			match &segments[index] {
+				Segment::AnyDepth => {
+					for (name, child) in node.items.iter() {
+						push_child(name, child, index);
+					}
+					stack.push((path.clone(), node_ref.clone(), index + 1));
+				}
+				Segment::Literal(name) => {
+					if let Some(child) = node.items.get(OsStr::new(name)) {
+						push_child(OsStr::new(name), child, index + 1);
+					}
+				}
+				segment @ Segment::Wildcard(_) => {
+					for (name, child) in node.items.iter() {
+						if segment.matches(name) {
+							push_child(name, child, index + 1);
+						}
+					}
+				}
+			}
+		}
+		out.into_iter().collect()
+	}
+
+	/// Returns the path of every node whose name f accepts, in the same order as `walk_all`
+	///
+	/// f is given the name of each node other than the root and its depth below the root, so
+	/// `/a/b` is checked as `b` at depth 2. This is the escape hatch for anything a glob can't
+	/// express, like running a regex over each name
+	pub fn find_matching<F: Fn(&OsStr, usize) -> bool>(&self, f: F) -> Vec<PathBuf> {
+		let mut out = Vec::new();
+		let mut stack = vec![(self.root_path(), 0, self.root.clone())];
+
+		while let Some((path, depth, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when matching names");
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				stack.push((path.join(name), depth + 1, child.clone()));
+			}
+			if path.file_name().is_some_and(|name| f(name, depth)) {
+				out.push(path);
+			}
+		}
+		out
+	}
+
+	/// Same as `find_matching` with each name checked against regex, in the same order
+	///
+	/// Names that are not valid UTF-8 never match. Needs the `regex` feature
+	#[cfg(feature = "regex")]
+	pub fn find_regex(&self, regex: &regex::Regex) -> Vec<PathBuf> {
+		self.find_matching(|name, _| name.to_str().is_some_and(|name| regex.is_match(name)))
+	}
+}
+
+/// Match name against the tokens of a segment
+fn wildcard_match(pattern: &[Token], name: &str) -> bool {
+	let name: Vec<char> = name.chars().collect();
+	let (mut p, mut n) = (0, 0);
+	// Position of the last `*` seen and where in name it started matching
+	let mut star = None;
+
+	while n < name.len() {
+		if let Some(Token::Star) = pattern.get(p) {
+			star = Some((p, n));
+			p += 1;
+		} else if pattern.get(p).is_some_and(|token| token.matches(name[n])) {
+			p += 1;
+			n += 1;
+		} else if let Some((star_p, star_n)) = star {
+			// Let the last `*` take one more character and try again
+			p = star_p + 1;
+			n = star_n + 1;
+			star = Some((star_p, star_n + 1));
+		} else {
+			return false;
+		}
+	}
+	pattern[p..].iter().all(|token| matches!(token, Token::Star))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::PathStore;
+	use std::path::PathBuf;
+
+	fn paths(paths: &[&str]) -> Vec<PathBuf> {
+		paths.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn glob() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f/FDrive/files.txt", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello.rs", None), Ok(true));
+		assert_eq!(store.add_path("/f/src/main.rs", None), Ok(true));
+		assert_eq!(store.add_path("/g/deep/er/lib.rs", None), Ok(true));
+
+		assert_eq!(store.glob("/f/*"), paths(&["/f/FDrive", "/f/src"]));
+		assert_eq!(store.glob("/f/*/*.rs"), paths(&["/f/FDrive/hello.rs", "/f/src/main.rs"]));
+		assert_eq!(store.glob("/**/*.rs"), paths(&[
+			"/f/FDrive/hello.rs",
+			"/f/src/main.rs",
+			"/g/deep/er/lib.rs",
+		]));
+		assert_eq!(store.glob("/g/**"), paths(&["/g", "/g/deep", "/g/deep/er", "/g/deep/er/lib.rs"]));
+		assert_eq!(store.glob("/**/**/er"), paths(&["/g/deep/er"]));
+		assert_eq!(store.glob("/*/F*e"), paths(&["/f/FDrive"]));
+		assert_eq!(store.glob("/f/FDrive/files.txt"), paths(&["/f/FDrive/files.txt"]));
+		assert!(store.glob("/h/*").is_empty());
+		assert!(store.glob("f/*").is_empty());
+
+		assert_eq!(store.glob("/h/../f/./FDrive/../*"), store.glob("/f/*"));
+		assert_eq!(store.glob("/./g/.."), paths(&["/"]));
+		assert!(store.glob("/*/../g").is_empty());
+		assert!(store.glob("/f/../../g").is_empty());
+	}
+
+	#[test]
+	fn find_matching() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &["/backups/backup-2020-01-31", "/backups/old/backup-1999-12-01", "/backups/backup-notes", "/b"] {
+			assert_eq!(store.add_path(path, None), Ok(true));
+		}
+
+		let is_dated = |name: &std::ffi::OsStr, _| {
+			let name = name.to_string_lossy();
+			name.strip_prefix("backup-").is_some_and(|date| {
+				date.len() == 10
+					&& date.chars().enumerate().all(|(i, c)| match i {
+						4 | 7 => c == '-',
+						_ => c.is_ascii_digit(),
+					})
+			})
+		};
+		assert_eq!(store.find_matching(is_dated), paths(&[
+			"/backups/backup-2020-01-31",
+			"/backups/old/backup-1999-12-01",
+		]));
+		assert_eq!(store.find_matching(|name, depth| depth == 1 && name.len() == 1), paths(&["/b"]));
+		assert!(store.find_matching(|_, _| false).is_empty());
+		assert_eq!(store.find_matching(|_, _| true).len(), store.size());
+	}
+
+	#[cfg(feature = "regex")]
+	#[test]
+	fn find_regex() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &["/backups/backup-2020-01-31", "/backups/old/backup-1999-12-01", "/backups/backup-notes"] {
+			assert_eq!(store.add_path(path, None), Ok(true));
+		}
+
+		let dated = regex::Regex::new(r"^backup-\d{4}-\d{2}-\d{2}$").unwrap();
+		assert_eq!(store.find_regex(&dated), paths(&[
+			"/backups/backup-2020-01-31",
+			"/backups/old/backup-1999-12-01",
+		]));
+		assert_eq!(store.find_regex(&regex::Regex::new("^b").unwrap()).len(), 4);
+	}
+
+	#[test]
+	fn glob_any_depth() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &["/projects/Cargo.toml", "/projects/a/Cargo.toml", "/projects/a/b/c/Cargo.toml", "/other/Cargo.toml"] {
+			assert_eq!(store.add_path(path, None), Ok(true));
+		}
+
+		// Middle, including zero components in between
+		assert_eq!(store.glob("/projects/**/Cargo.toml"), paths(&[
+			"/projects/Cargo.toml",
+			"/projects/a/Cargo.toml",
+			"/projects/a/b/c/Cargo.toml",
+		]));
+		// Start
+		assert_eq!(store.glob("/**/c/Cargo.toml"), paths(&["/projects/a/b/c/Cargo.toml"]));
+		assert_eq!(store.glob("/**/Cargo.toml").len(), 4);
+		// End, which includes the node before the `**`
+		assert_eq!(store.glob("/projects/a/b/**"), paths(&[
+			"/projects/a/b",
+			"/projects/a/b/c",
+			"/projects/a/b/c/Cargo.toml",
+		]));
+		assert_eq!(store.glob("/**"), store.walk_all());
+		// Redundant but legal, each path only comes back once
+		assert_eq!(store.glob("/**/**/c"), paths(&["/projects/a/b/c"]));
+		assert_eq!(store.glob("/projects/**/**/Cargo.toml"), store.glob("/projects/**/Cargo.toml"));
+		assert_eq!(store.glob("/**/a/**/b"), paths(&["/projects/a/b"]));
+		assert!(store.glob("/**/missing/**").is_empty());
+	}
+
+	#[test]
+	fn glob_classes() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &["/home/a1/x.iso", "/home/a2/y.iso", "/home/b1/z.iso", "/home/[x]/w.iso", "/home/-"] {
+			assert_eq!(store.add_path(path, None), Ok(true));
+		}
+
+		assert_eq!(store.glob("/home/a?/*.iso"), paths(&["/home/a1/x.iso", "/home/a2/y.iso"]));
+		assert_eq!(store.glob("/home/?"), paths(&["/home/-"]));
+		assert_eq!(store.glob("/home/[ab]1"), paths(&["/home/a1", "/home/b1"]));
+		assert_eq!(store.glob("/home/[a-b][2-9]"), paths(&["/home/a2"]));
+		assert_eq!(store.glob("/home/[!a]*/*"), paths(&["/home/[x]/w.iso", "/home/b1/z.iso"]));
+		assert_eq!(store.glob("/home/[^ab[]*"), paths(&["/home/-"]));
+		assert_eq!(store.glob("/home/[]x[]*"), paths(&["/home/[x]"]));
+		assert_eq!(store.glob("/home/[-]"), paths(&["/home/-"]));
+		// An unclosed class is matched literally
+		assert!(store.glob("/home/[a1").is_empty());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn glob_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/f/not\xffutf8"), None), Ok(true));
+		assert_eq!(store.add_path("/f/utf8", None), Ok(true));
+		assert_eq!(store.glob("/f/*"), paths(&["/f/utf8"]));
+		assert_eq!(store.glob("/f/**").len(), 3);
+	}
+}
@@ -0,0 +1,179 @@
+use crate::glob::{segments_match, Segment};
+use crate::PathStore;
+
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+
+/// A list of gitignore style patterns, used with `PathStore::walk_ignoring`
+///
+/// Patterns use the same wildcards as `PathStore::glob`. A pattern with no `/` other than a
+/// trailing one matches a name at any depth, otherwise it matches the whole path from the root of
+/// the store. A trailing `/` only matches nodes with children, and a leading `!` includes again
+/// something an earlier pattern ignored. The last pattern that matches wins
+#[derive(Default)]
+pub struct IgnoreSet {
+	rules: Vec<Rule>,
+}
+
+struct Rule {
+	segments: Vec<Segment>,
+	negated: bool,
+	dir_only: bool,
+	/// Matched against the whole path rather than just the name
+	anchored: bool,
+}
+
+impl IgnoreSet {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Parse the contents of a gitignore file, one pattern per line
+	///
+	/// Blank lines and lines starting with `#` are skipped
+	pub fn parse(text: &str) -> Self {
+		let mut set = Self::new();
+		for line in text.lines() {
+			set.add(line);
+		}
+		set
+	}
+
+	/// Add a single pattern, after every pattern already in the set
+	///
+	/// Start the pattern with `\` to match a name starting with `#` or `!`
+	pub fn add(&mut self, pattern: &str) {
+		let pattern = pattern.trim_end();
+		if pattern.is_empty() || pattern.starts_with('#') {
+			return;
+		}
+		let (negated, pattern) = match pattern.strip_prefix('!') {
+			Some(pattern) => (true, pattern),
+			None => (false, pattern.strip_prefix('\\').unwrap_or(pattern)),
+		};
+		let (dir_only, pattern) = match pattern.strip_suffix('/') {
+			Some(pattern) => (true, pattern),
+			None => (false, pattern),
+		};
+		self.rules.push(Rule {
+			segments: Segment::parse_all(pattern),
+			negated,
+			dir_only,
+			anchored: pattern.contains('/'),
+		});
+	}
+
+	/// Returns true if path, taken relative to the root of the store, is ignored
+	///
+	/// is_dir says whether trailing `/` patterns apply. Only path itself is checked, with
+	/// `target/` ignored `target/debug` is not, though `walk_ignoring` never gets that far
+	pub fn is_ignored<P: AsRef<Path>>(&self, path: P, is_dir: bool) -> bool {
+		self.is_ignored_names(&names(path.as_ref()), is_dir)
+	}
+
+	fn is_ignored_names(&self, names: &[&OsStr], is_dir: bool) -> bool {
+		let name = match names.last() {
+			Some(name) => name,
+			None => return false,
+		};
+		let matching = self.rules.iter().rev().find(|rule| {
+			(is_dir || !rule.dir_only)
+				&& if rule.anchored {
+					segments_match(&rule.segments, names)
+				} else {
+					segments_match(&rule.segments, &[name])
+				}
+		});
+		matching.is_some_and(|rule| !rule.negated)
+	}
+}
+
+impl<T> PathStore<T> {
+	/// Same as `walk` but leaves out everything patterns ignores
+	///
+	/// Nodes with children count as directories. An ignored directory is skipped along with
+	/// everything under it without being visited, so ignoring `node_modules/` costs nothing however
+	/// big it is. Like git, a `!` pattern can't include again something under an ignored directory
+	pub fn walk_ignoring(&self, patterns: &IgnoreSet) -> Vec<PathBuf> {
+		let mut out = Vec::new();
+		let mut stack = vec![(self.root_path(), self.root.clone())];
+
+		while let Some((path, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when walking tree");
+			if patterns.is_ignored_names(&names(&path), !node.items.is_empty()) {
+				continue;
+			}
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				stack.push((path.join(name), child.clone()));
+			}
+			if node.items.is_empty() || node.data.is_some() {
+				out.push(path);
+			}
+		}
+		out
+	}
+}
+
+/// The names in path, without any root
+fn names(path: &Path) -> Vec<&OsStr> {
+	path.components()
+		.filter_map(|c| match c {
+			Component::Normal(name) => Some(name),
+			_ => None,
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::IgnoreSet;
+	use crate::PathStore;
+	use std::path::PathBuf;
+
+	fn paths(paths: &[&str]) -> Vec<PathBuf> {
+		paths.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn is_ignored() {
+		let set = IgnoreSet::parse("# build output\ntarget/\n*.o\n!keep.o\n/docs/*.html\n\n\\#notes\n");
+
+		assert!(set.is_ignored("target", true));
+		assert!(!set.is_ignored("target", false));
+		assert!(set.is_ignored("crates/a/target", true));
+		assert!(set.is_ignored("src/main.o", false));
+		assert!(!set.is_ignored("src/keep.o", false));
+		assert!(set.is_ignored("docs/index.html", false));
+		assert!(!set.is_ignored("src/docs/index.html", false));
+		assert!(set.is_ignored("#notes", false));
+		assert!(!set.is_ignored("", true));
+		assert!(!IgnoreSet::new().is_ignored("anything", false));
+	}
+
+	#[test]
+	fn walk_ignoring() {
+		let mut store = PathStore::new(None::<()>);
+		for path in &[
+			"/src/main.rs",
+			"/src/main.o",
+			"/src/keep.o",
+			"/target/debug/app",
+			"/node_modules/a/b/c",
+			"/docs/index.html",
+			"/docs/a/index.html",
+			"/target",
+		] {
+			store.add_path(path, None).unwrap();
+		}
+
+		let set = IgnoreSet::parse("target/\nnode_modules/\n*.o\n!keep.o\n/docs/*.html");
+		assert_eq!(store.walk_ignoring(&set), paths(&["/docs/a/index.html", "/src/keep.o", "/src/main.rs"]));
+
+		// A negated file under an ignored directory stays ignored
+		let set = IgnoreSet::parse("src/\n!src/main.rs");
+		assert!(!store.walk_ignoring(&set).contains(&PathBuf::from("/src/main.rs")));
+
+		assert_eq!(store.walk_ignoring(&IgnoreSet::new()), store.walk());
+	}
+}
@@ -0,0 +1,339 @@
+use crate::{PathNode, PathNodeRef, PathStore};
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::mem;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Weak;
+
+/// Depth first traversal shared by the borrowing iterators
+///
+/// Keeps a single path buffer that is pushed and popped as it moves around the tree, so deep
+/// trees don't need a new path allocated for every node on the way down
+pub(crate) struct Walker<T> {
+	/// Depth below the root, name (None for the root) and node still to visit
+	stack: Vec<(usize, Option<OsString>, PathNodeRef<T>)>,
+	path: PathBuf,
+	depth: usize,
+	/// Children of nodes this deep below the root are not visited
+	max_depth: usize,
+}
+
+impl<T> Walker<T> {
+	fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self::starting_at(path, root, usize::MAX)
+	}
+
+	/// Walk the subtree under node, which is at path, depths are counted from node
+	pub(crate) fn starting_at(path: PathBuf, node: PathNodeRef<T>, max_depth: usize) -> Self {
+		Self {
+			stack: vec![(0, None, node)],
+			path,
+			depth: 0,
+			max_depth,
+		}
+	}
+
+	/// Visit nodes until f gives back a value, each node is only locked while f looks at it
+	///
+	/// f is also given the depth of the node below the root
+	pub(crate) fn next_with<R, F: FnMut(&Path, usize, &PathNode<T>) -> Option<R>>(&mut self, mut f: F) -> Option<R> {
+		while let Some((depth, name, node)) = self.stack.pop() {
+			if let Some(name) = name {
+				while self.depth >= depth {
+					self.path.pop();
+					self.depth -= 1;
+				}
+				self.path.push(name);
+				self.depth = depth;
+			}
+
+			let node = node.read().expect("Failed to lock tree node when walking tree");
+
+			if depth < self.max_depth {
+				// Reversed so children come off the stack in sorted order
+				for (name, child) in node.items.iter().rev() {
+					self.stack.push((depth + 1, Some(name.clone()), child.clone()));
+				}
+			}
+
+			if let Some(out) = f(&self.path, depth, &node) {
+				return Some(out);
+			}
+		}
+		None
+	}
+}
+
+/// Lazy depth first iterator over the same paths as `PathStore::walk`
+///
+/// Created with `PathStore::iter`
+pub struct Paths<'a, T> {
+	walker: Walker<T>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> Paths<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self::with_max_depth(path, root, usize::MAX)
+	}
+
+	/// Nodes at max_depth are treated as leaves
+	pub(crate) fn with_max_depth(path: PathBuf, root: PathNodeRef<T>, max_depth: usize) -> Self {
+		Self {
+			walker: Walker::starting_at(path, root, max_depth),
+			_store: PhantomData,
+		}
+	}
+
+	/// Only walk the subtree under node, which is at path
+	pub(crate) fn under(path: PathBuf, node: PathNodeRef<T>) -> Self {
+		Self {
+			walker: Walker::starting_at(path, node, usize::MAX),
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for Paths<'a, T> {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<PathBuf> {
+		let max_depth = self.walker.max_depth;
+		self.walker.next_with(|path, depth, node| {
+			if node.items.is_empty() || node.data.is_some() || depth == max_depth {
+				Some(path.to_path_buf())
+			} else {
+				None
+			}
+		})
+	}
+}
+
+/// Lazy depth first iterator over every node, parents before their children
+///
+/// Created with `PathStore::iter_all`
+pub struct AllPaths<'a, T> {
+	walker: Walker<T>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> AllPaths<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self {
+			walker: Walker::new(path, root),
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for AllPaths<'a, T> {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<PathBuf> {
+		self.walker.next_with(|path, _, _| Some(path.to_path_buf()))
+	}
+}
+
+/// Lazy depth first iterator over the path of every node with data
+///
+/// Created with `PathStore::iter_data_paths`
+pub struct DataPaths<'a, T> {
+	walker: Walker<T>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> DataPaths<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self {
+			walker: Walker::new(path, root),
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for DataPaths<'a, T> {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<PathBuf> {
+		self.walker.next_with(|path, _, node| node.data.as_ref().map(|_| path.to_path_buf()))
+	}
+}
+
+/// Lazy depth first iterator over every path with data, along with a clone of the data
+///
+/// Created with `PathStore::iter_entries`
+pub struct Entries<'a, T> {
+	walker: Walker<T>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> Entries<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self {
+			walker: Walker::new(path, root),
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T: Clone> Iterator for Entries<'a, T> {
+	type Item = (PathBuf, T);
+
+	fn next(&mut self) -> Option<(PathBuf, T)> {
+		self.walker.next_with(|path, _, node| node.data.clone().map(|data| (path.to_path_buf(), data)))
+	}
+}
+
+/// Lazy depth first iterator over every node, children before their parents
+///
+/// Created with `PathStore::iter_postorder`
+pub struct PostOrder<'a, T> {
+	/// The flag is set once a node's children have been pushed
+	stack: Vec<(PathBuf, PathNodeRef<T>, bool)>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> PostOrder<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		Self {
+			stack: vec![(path, root, false)],
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for PostOrder<'a, T> {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<PathBuf> {
+		while let Some((path, node, expanded)) = self.stack.pop() {
+			if expanded {
+				return Some(path);
+			}
+
+			let children: Vec<_> = node
+				.read()
+				.expect("Failed to lock tree node when walking tree")
+				.items
+				.iter()
+				.rev()
+				.map(|(name, child)| (path.join(name), child.clone(), false))
+				.collect();
+			self.stack.push((path, node, true));
+			self.stack.extend(children);
+		}
+		None
+	}
+}
+
+/// Lazy iterator over the paths above a node, nearest first
+///
+/// Created with `PathStore::ancestors`, follows the weak parent link of each node
+pub struct Ancestors<'a, T> {
+	/// Path of node, popped as the iterator moves up
+	path: PathBuf,
+	node: PathNodeRef<T>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> Ancestors<'a, T> {
+	pub(crate) fn new(path: PathBuf, node: PathNodeRef<T>) -> Self {
+		Self {
+			path,
+			node,
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for Ancestors<'a, T> {
+	type Item = PathBuf;
+
+	fn next(&mut self) -> Option<PathBuf> {
+		let parent = self
+			.node
+			.read()
+			.expect("Failed to lock tree node when finding ancestors")
+			.parent
+			.as_ref()
+			.and_then(Weak::upgrade)?;
+		if !self.path.pop() {
+			return None;
+		}
+		self.node = parent;
+		Some(self.path.clone())
+	}
+}
+
+/// Lazy breadth first iterator over every node, along with its depth below the root
+///
+/// Created with `PathStore::iter_bfs`
+pub struct BreadthFirst<'a, T> {
+	queue: VecDeque<(usize, PathBuf, PathNodeRef<T>)>,
+	_store: PhantomData<&'a PathStore<T>>,
+}
+
+impl<'a, T> BreadthFirst<'a, T> {
+	pub(crate) fn new(path: PathBuf, root: PathNodeRef<T>) -> Self {
+		let mut queue = VecDeque::new();
+		queue.push_back((0, path, root));
+		Self {
+			queue,
+			_store: PhantomData,
+		}
+	}
+}
+
+impl<'a, T> Iterator for BreadthFirst<'a, T> {
+	type Item = (usize, PathBuf);
+
+	fn next(&mut self) -> Option<(usize, PathBuf)> {
+		let (depth, path, node) = self.queue.pop_front()?;
+		let node = node.read().expect("Failed to lock tree node when walking tree");
+		for (name, child) in node.items.iter() {
+			self.queue.push_back((depth + 1, path.join(name), child.clone()));
+		}
+		Some((depth, path))
+	}
+}
+
+/// Consuming iterator over every path with data, moving the data out
+///
+/// Created by `PathStore::into_iter`, yields in the same order as `PathStore::walk_with_data`
+pub struct IntoIter<T> {
+	stack: Vec<(PathBuf, PathNodeRef<T>)>,
+}
+
+impl<T> IntoIter<T> {
+	/// Drain node and everything under it, with path as the path of node
+	pub(crate) fn new(path: PathBuf, node: PathNodeRef<T>) -> Self {
+		Self {
+			stack: vec![(path, node)],
+		}
+	}
+}
+
+impl<T> Iterator for IntoIter<T> {
+	type Item = (PathBuf, T);
+
+	fn next(&mut self) -> Option<(PathBuf, T)> {
+		while let Some((path, node)) = self.stack.pop() {
+			// Taken through the lock rather than unwrapping, the node is freed either way once it goes out of scope
+			let (items, data) = {
+				let mut node = node.write().unwrap();
+				(mem::take(&mut node.items), node.data.take())
+			};
+
+			for (name, child) in items.into_iter().rev() {
+				self.stack.push((path.join(name), child));
+			}
+
+			if let Some(data) = data {
+				return Some((path, data));
+			}
+		}
+		None
+	}
+}
@@ -0,0 +1,237 @@
+use crate::{check_name, PathNode, PathNodeRef, PathStore, StorageError};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::ffi::{OsStr, OsString};
+use std::io;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+impl<T: Serialize + DeserializeOwned> PathStore<T> {
+	/// The tree as nested JSON objects, each node as `{"name": ..., "data": ..., "children": [...]}`
+	///
+	/// Children are in sorted order, so the same store always gives the same value. The root is
+	/// named `/`, or `.` in a relative store, and nodes without data have `null` data. Names that
+	/// are not valid UTF-8 have anything invalid replaced by U+FFFD in `name`, and the node gets a
+	/// `name_bytes` array with the name as it is stored (the raw bytes on Unix) so it can be read
+	/// back exactly. Only the paths and data are written, not settings like the base
+	///
+	/// # Panics
+	/// If data can't be turned into JSON, like a map with keys that aren't strings
+	pub fn to_json_value(&self) -> Value {
+		let root = self.root.read().expect("Failed to lock tree node when writing JSON");
+		let mut stack = vec![JsonFrame::new(&self.root_label(), &root)];
+		drop(root);
+		loop {
+			let frame = stack.last_mut().expect("Stack always has the root until it is returned");
+			if let Some((name, child)) = frame.pending.next() {
+				let child = child.read().expect("Failed to lock tree node when writing JSON");
+				stack.push(JsonFrame::new(&name, &child));
+				continue;
+			}
+			let value = stack.pop().expect("Stack always has the root until it is returned").finish();
+			match stack.last_mut() {
+				Some(parent) => parent.children.push(value),
+				None => return value,
+			}
+		}
+	}
+
+	/// Rebuild a store from the output of `to_json_value`
+	///
+	/// A root named `.` gives a relative store, `/` the default one. Missing `data` and
+	/// `children` are the same as `null` and no children, other fields are ignored. Data that is
+	/// `null` is read back as None. `name_bytes` is used over `name` when it is there, and is
+	/// only accepted on Unix. Names `add_path` would reject give `InvalidComponent`, a name that
+	/// appears twice under the same node gives `TargetExists`, and JSON that doesn't have this
+	/// shape gives `Io` with `InvalidData`
+	pub fn from_json_value(value: Value) -> Result<Self, StorageError> {
+		let (name, data, children) = json_node(value, &PathBuf::new())?;
+		let mut store = if name == "." {
+			PathStore::new_relative(data)
+		} else if name == "/" {
+			PathStore::new(data)
+		} else {
+			return Err(invalid_data(format!("Root is named {:?} rather than / or .", name)));
+		};
+		let mut stack = vec![(store.root_path(), children)];
+		while let Some((path, children)) = stack.pop() {
+			for child in children {
+				let (name, data, children) = json_node(child, &path)?;
+				check_name(&name)?;
+				let path = path.join(name);
+				if !store.add_path(&path, data)? {
+					return Err(StorageError::TargetExists { path });
+				}
+				stack.push((path, children));
+			}
+		}
+		Ok(store)
+	}
+}
+
+/// Node whose object is written once all of its children are
+struct JsonFrame<T> {
+	object: Map<String, Value>,
+	children: Vec<Value>,
+	pending: std::vec::IntoIter<(OsString, PathNodeRef<T>)>,
+}
+
+impl<T: Serialize> JsonFrame<T> {
+	fn new(name: &OsStr, node: &PathNode<T>) -> Self {
+		let mut object = Map::new();
+		object.insert(String::from("name"), Value::String(name.to_string_lossy().into_owned()));
+		if name.to_str().is_none() {
+			object.insert(String::from("name_bytes"), Value::from(name.as_encoded_bytes()));
+		}
+		let data = serde_json::to_value(&node.data).expect("Failed to turn data into JSON");
+		object.insert(String::from("data"), data);
+		let pending: Vec<_> = node.items.iter().map(|(name, child)| (name.clone(), child.clone())).collect();
+		Self {
+			object,
+			children: Vec::with_capacity(pending.len()),
+			pending: pending.into_iter(),
+		}
+	}
+
+	fn finish(mut self) -> Value {
+		self.object.insert(String::from("children"), Value::Array(self.children));
+		Value::Object(self.object)
+	}
+}
+
+/// Name, data and children of a node, parent is only used in errors
+fn json_node<T: DeserializeOwned>(value: Value, parent: &Path) -> Result<(OsString, Option<T>, Vec<Value>), StorageError> {
+	let mut object = match value {
+		Value::Object(object) => object,
+		_ => return Err(invalid_data(format!("Node under {} is not an object", parent.display()))),
+	};
+	let name = match (object.remove("name_bytes"), object.remove("name")) {
+		(Some(bytes), _) => {
+			let bytes = serde_json::from_value(bytes)
+				.map_err(|_| invalid_data(format!("Node under {} has name_bytes that are not bytes", parent.display())))?;
+			name_from_bytes(bytes)?
+		}
+		(None, Some(Value::String(name))) => OsString::from(name),
+		(None, _) => return Err(invalid_data(format!("Node under {} has no name", parent.display()))),
+	};
+	let data = match object.remove("data") {
+		Some(data) => serde_json::from_value(data)
+			.map_err(|error| invalid_data(format!("Data of {} under {}: {}", name.to_string_lossy(), parent.display(), error)))?,
+		None => None,
+	};
+	let children = match object.remove("children") {
+		Some(Value::Array(children)) => children,
+		None => Vec::new(),
+		Some(_) => {
+			return Err(invalid_data(format!("Children of {} under {} are not an array", name.to_string_lossy(), parent.display())))
+		}
+	};
+	Ok((name, data, children))
+}
+
+fn invalid_data(message: String) -> StorageError {
+	StorageError::Io {
+		kind: io::ErrorKind::InvalidData,
+		message,
+	}
+}
+
+#[cfg(unix)]
+fn name_from_bytes(bytes: Vec<u8>) -> Result<OsString, StorageError> {
+	Ok(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn name_from_bytes(_: Vec<u8>) -> Result<OsString, StorageError> {
+	Err(invalid_data(String::from("Names from name_bytes can only be read back on Unix")))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{InvalidComponentReason, PathStore, StorageError};
+	use serde_json::json;
+	use std::io;
+	use std::path::PathBuf;
+
+	#[test]
+	fn json_round_trip() {
+		let mut store = PathStore::new(Some(0));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/b", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/a", None), Ok(true));
+		let value = store.to_json_value();
+		assert_eq!(
+			value,
+			json!({"name": "/", "data": 0, "children": [
+				{"name": "f", "data": null, "children": [
+					{"name": "a", "data": null, "children": []},
+					{"name": "b", "data": 1, "children": []},
+				]},
+				{"name": "g", "data": null, "children": []},
+			]})
+		);
+		let copy = PathStore::<i32>::from_json_value(value).unwrap();
+		assert!(copy == store);
+		assert_eq!(copy.size(), store.size());
+
+		let mut relative = PathStore::new_relative(None);
+		assert_eq!(relative.add_path("src/lib.rs", Some(2)), Ok(true));
+		let value = relative.to_json_value();
+		assert_eq!(value["name"], ".");
+		let copy = PathStore::<i32>::from_json_value(value).unwrap();
+		assert!(copy == relative);
+		assert_eq!(copy.walk(), vec![PathBuf::from("src/lib.rs")]);
+
+		// Missing fields are the same as empty ones
+		let short = PathStore::<i32>::from_json_value(json!({"name": "/", "children": [{"name": "a", "extra": true}]})).unwrap();
+		assert_eq!(short.walk(), vec![PathBuf::from("/a")]);
+	}
+
+	#[test]
+	fn json_bad_input() {
+		let twice = json!({"name": "/", "children": [{"name": "a"}, {"name": "a"}]});
+		assert_eq!(
+			PathStore::<i32>::from_json_value(twice).err().unwrap(),
+			StorageError::TargetExists { path: PathBuf::from("/a") }
+		);
+		let slash = json!({"name": "/", "children": [{"name": "a/b"}]});
+		assert_eq!(
+			PathStore::<i32>::from_json_value(slash).err().unwrap(),
+			StorageError::InvalidComponent {
+				component: "a/b".into(),
+				reason: InvalidComponentReason::ContainsSeparator
+			}
+		);
+		for value in [
+			json!([]),
+			json!({"name": "root"}),
+			json!({"name": "/", "children": {}}),
+			json!({"name": "/", "children": [{"data": 1}]}),
+			json!({"name": "/", "children": [{"name": "a", "data": "1"}]}),
+		] {
+			match PathStore::<i32>::from_json_value(value) {
+				Err(StorageError::Io { kind: io::ErrorKind::InvalidData, .. }) => {}
+				other => panic!("{:?}", other.map(|store| store.walk())),
+			}
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn json_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/f/not\xffutf8"), Some(1)), Ok(true));
+		let value = store.to_json_value();
+		let child = &value["children"][0]["children"][0];
+		assert_eq!(child["name"], "not\u{fffd}utf8");
+		assert_eq!(child["name_bytes"], json!(b"not\xffutf8".to_vec()));
+		let copy = PathStore::<i32>::from_json_value(value).unwrap();
+		assert!(copy == store);
+	}
+}
@@ -1,30 +1,60 @@
+mod binary;
+mod diff;
+mod entry;
 mod errors;
+mod export;
+mod fuzzy;
+mod glob;
+mod ignore;
+mod iter;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "rayon")]
+mod par;
+mod sets;
+#[cfg(feature = "serde")]
+mod serialize;
 
-use errors::StorageError;
+pub use diff::PathDiff;
+pub use entry::{DataMut, Entry, OccupiedEntry, VacantEntry};
+pub use iter::{AllPaths, Ancestors, BreadthFirst, DataPaths, Entries, IntoIter, Paths, PostOrder};
+pub use errors::{InvalidComponentReason, StorageError};
+pub use ignore::IgnoreSet;
+#[cfg(feature = "serde")]
+pub use serialize::flat as serde_flat;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::ffi::{OsStr, OsString};
-use std::path::{Path, PathBuf};
-use std::rc::{Rc, Weak};
-use std::sync::RwLock;
-use std::hash::Hash;
+use std::iter::FromIterator;
+use std::ops::Bound;
+use std::mem;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf, Prefix};
+use std::sync::{Arc, PoisonError, RwLock, Weak};
 
-type PathNodeRef<T> = Rc<RwLock<PathNode<T>>>;
+type PathNodeRef<T> = Arc<RwLock<PathNode<T>>>;
 type PathNodeRefWeak<T> = Weak<RwLock<PathNode<T>>>;
 
 struct PathNode<T> {
 	//	name: OsString,
 	data: Option<T>,
-	items: HashMap<OsString, PathNodeRef<T>>,
-//	parent: Option<PathNodeRefWeak<T>>,
+	/// Sorted so walks are deterministic
+	items: BTreeMap<OsString, PathNodeRef<T>>,
+	/// Case folded names of the children and the name each is stored under in items, only kept in
+	/// stores that ignore case
+	folded: BTreeMap<OsString, OsString>,
+	/// None for the root
+	parent: Option<PathNodeRefWeak<T>>,
+	/// Number of nodes below this one, kept up to date by everything that adds or removes nodes
+	descendants: usize,
 }
 
 impl<T> PathNode<T> {
-	/// Creates the root path node
+//	/// Creates the root path node
 //	pub fn root(data: Option<T>) -> Self {
 //		Self {
 ////			name: OsString::from("/"),
-//			items: HashMap::new(),
+//			items: BTreeMap::new(),
 //			data,
 ////			parent: None,
 //		}
@@ -33,136 +63,3851 @@ impl<T> PathNode<T> {
 	pub fn new(data: Option<T>) -> Self {
 		Self {
 //			name,
-			items: HashMap::new(),
+			items: BTreeMap::new(),
+			folded: BTreeMap::new(),
 			data,
-//			parent: Some(parent),
+			parent: None,
+			descendants: 0,
 		}
 	}
 
+	/// Creates a node to be added under parent
+	fn with_parent(data: Option<T>, parent: &PathNodeRef<T>) -> PathNodeRef<T> {
+		Arc::new(RwLock::new(Self {
+			items: BTreeMap::new(),
+			folded: BTreeMap::new(),
+			data,
+			parent: Some(Arc::downgrade(parent)),
+			descendants: 0,
+		}))
+	}
+
 	pub fn set_data(&mut self, data: Option<T>) {
 		self.data = data;
 	}
+
+	/// Find the child called name, along with the name it is stored under
+	///
+	/// With case_insensitive a child whose name only differs in case is found if there is no exact
+	/// match, by looking its folded name up in folded
+	fn child(&self, name: &OsStr, case_insensitive: bool) -> Option<(&OsString, &PathNodeRef<T>)> {
+		if let Some(child) = self.items.get_key_value(name) {
+			return Some(child);
+		}
+		if !case_insensitive {
+			return None;
+		}
+		let key = self.folded.get(&fold_case(name))?;
+		self.items.get_key_value(key)
+	}
+
+	/// Add child under name, and to folded if the store ignores case
+	fn insert_child(&mut self, name: OsString, child: PathNodeRef<T>, case_insensitive: bool) {
+		if case_insensitive {
+			self.folded.insert(fold_case(&name), name.clone());
+		}
+		self.items.insert(name, child);
+	}
+
+	/// Remove the child stored under exactly name
+	fn remove_child(&mut self, name: &OsStr) -> Option<PathNodeRef<T>> {
+		let child = self.items.remove(name)?;
+		if !self.folded.is_empty() {
+			self.folded.remove(&fold_case(name));
+		}
+		Some(child)
+	}
+
+	/// Drop the folded names of children that were removed from items directly
+	fn retain_folded(&mut self) {
+		let items = &self.items;
+		self.folded.retain(|_, name| items.contains_key(name));
+	}
+
+	/// Rebuild folded for node and everything under it, for a subtree moved in from a store
+	/// that doesn't ignore case the same way
+	fn refold(node: &PathNodeRef<T>, case_insensitive: bool) {
+		let mut stack = vec![node.clone()];
+		while let Some(node) = stack.pop() {
+			let mut node = node.write().unwrap();
+			node.folded = if case_insensitive {
+				node.items.keys().map(|name| (fold_case(name), name.clone())).collect()
+			} else {
+				BTreeMap::new()
+			};
+			stack.extend(node.items.values().cloned());
+		}
+	}
+
+	/// Add count to the cached descendants of node and every node above it
+	///
+	/// None of them may be locked
+	fn add_descendants(node: &PathNodeRef<T>, count: usize) {
+		Self::update_descendants(node, |descendants| descendants + count);
+	}
+
+	/// Take count off the cached descendants of node and every node above it
+	///
+	/// None of them may be locked
+	fn remove_descendants(node: &PathNodeRef<T>, count: usize) {
+		Self::update_descendants(node, |descendants| descendants - count);
+	}
+
+	fn update_descendants<F: Fn(usize) -> usize>(node: &PathNodeRef<T>, f: F) {
+		let mut current = Some(node.clone());
+		while let Some(node) = current {
+			let mut node = node.write().unwrap();
+			node.descendants = f(node.descendants);
+			current = node.parent.as_ref().and_then(Weak::upgrade);
+		}
+	}
+
+	/// Work out the cached descendants of node and everything under it again, returns the count for node
+	///
+	/// For changes that touch too much of the tree to update counts as they go
+	fn recount(node: &PathNodeRef<T>) -> usize {
+		// Every node comes after its parent, so going backwards counts children before parents
+		let mut order = vec![node.clone()];
+		let mut i = 0;
+		while i < order.len() {
+			let children: Vec<_> = order[i]
+				.read()
+				.expect("Failed to lock tree node when counting nodes")
+				.items
+				.values()
+				.cloned()
+				.collect();
+			order.extend(children);
+			i += 1;
+		}
+		for node in order.iter().rev() {
+			let mut node = node.write().unwrap();
+			node.descendants = node
+				.items
+				.values()
+				.map(|child| 1 + child.read().expect("Failed to lock tree node when counting nodes").descendants)
+				.sum();
+		}
+		let descendants = node.read().expect("Failed to lock tree node when counting nodes").descendants;
+		descendants
+	}
+
+	/// Copy this node and everything under it into new nodes, the copy has no parent
+	fn deep_clone(&self) -> PathNodeRef<T>
+	where
+		T: Clone,
+	{
+		self.map_copy(T::clone)
+	}
+
+	/// Copy this node and everything under it into new nodes with f applied to the data
+	///
+	/// The copy has no parent
+	fn map_copy<U, F: FnMut(&T) -> U>(&self, mut f: F) -> PathNodeRef<U> {
+		let out = Arc::new(RwLock::new(PathNode::new(self.data.as_ref().map(&mut f))));
+		out.write().unwrap().descendants = self.descendants;
+
+		// Pairs of original and copied nodes whose children still need copying
+		let mut stack = Vec::new();
+		fn copy_children<T, U, F: FnMut(&T) -> U>(
+			from: &PathNode<T>,
+			to: &PathNodeRef<U>,
+			f: &mut F,
+			stack: &mut Vec<(PathNodeRef<T>, PathNodeRef<U>)>,
+		) {
+			let mut to_lock = to.write().unwrap();
+			to_lock.folded = from.folded.clone();
+			for (name, child) in from.items.iter() {
+				let child_lock = child.read().expect("Failed to lock tree node when cloning tree");
+				let copy = PathNode::with_parent(child_lock.data.as_ref().map(&mut *f), to);
+				copy.write().unwrap().descendants = child_lock.descendants;
+				drop(child_lock);
+				to_lock.items.insert(name.clone(), copy.clone());
+				stack.push((child.clone(), copy));
+			}
+		}
+
+		copy_children(self, &out, &mut f, &mut stack);
+		while let Some((from, to)) = stack.pop() {
+			let from = from.read().expect("Failed to lock tree node when cloning tree");
+			copy_children(&from, &to, &mut f, &mut stack);
+		}
+		out
+	}
+
+	/// Remove every child subtree without data anywhere in it, returns the number of nodes removed
+	fn prune(&mut self) -> usize {
+		let mut removed = 0;
+		self.items.retain(|_, child| {
+			let mut child = child.write().unwrap();
+			removed += child.prune();
+			if child.items.is_empty() && child.data.is_none() {
+				removed += 1;
+				false
+			} else {
+				true
+			}
+		});
+		self.retain_folded();
+		self.descendants -= removed;
+		removed
+	}
+
+	/// Number of nodes below this one that match, not counting itself
+	fn count_descendants_where<F: Fn(&PathNode<T>) -> bool>(&self, matches: F) -> usize {
+		let mut count = 0;
+		let mut stack: Vec<_> = self.items.values().cloned().collect();
+		while let Some(node) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when counting nodes");
+			if matches(&node) {
+				count += 1;
+			}
+			stack.extend(node.items.values().cloned());
+		}
+		count
+	}
+}
+
+/// Names of the nodes along a resolved path below the root
+///
+/// A Windows prefix and the root after it become a single node, named by `prefix_name`, so the
+/// different ways of writing the same drive or share end up in the same place
+fn node_names(path: &Path) -> Vec<Cow<'_, OsStr>> {
+	path.components()
+		.filter_map(|item| match item {
+			Component::Prefix(prefix) => Some(Cow::Owned(prefix_name(prefix.kind()))),
+			Component::RootDir => None,
+			item => Some(Cow::Borrowed(item.as_os_str())),
+		})
+		.collect()
+}
+
+/// Name of the node a Windows prefix is stored under
+///
+/// Verbatim forms are stored as their plain equivalent, `\\?\C:\` is `C:\` and `\\?\UNC\server\share`
+/// is `\\server\share\`. Drive letters are upper cased. The name ends in a separator so pushing it onto
+/// a path gives back a valid absolute path
+fn prefix_name(prefix: Prefix<'_>) -> OsString {
+	let mut name = match prefix {
+		Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => {
+			OsString::from(format!("{}:", char::from(drive.to_ascii_uppercase())))
+		}
+		Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+			let mut name = OsString::from(r"\\");
+			name.push(server);
+			name.push(r"\");
+			name.push(share);
+			name
+		}
+		Prefix::Verbatim(name) => {
+			let mut out = OsString::from(r"\\?\");
+			out.push(name);
+			out
+		}
+		Prefix::DeviceNS(name) => {
+			let mut out = OsString::from(r"\\.\");
+			out.push(name);
+			out
+		}
+	};
+	name.push(r"\");
+	name
+}
+
+/// Push each component of the relative path onto start, resolving `..` without going above start
+///
+/// escapes gives the error for a `..` that would go above start, rooted if path has a root or prefix
+fn join_lexically<E, R>(mut start: PathBuf, path: &Path, escapes: E, rooted: R) -> Result<PathBuf, StorageError>
+where
+	E: FnOnce() -> StorageError,
+	R: FnOnce() -> StorageError,
+{
+	let mut depth = 0;
+	for item in path.components() {
+		match item {
+			Component::Normal(name) => {
+				start.push(name);
+				depth += 1;
+			}
+			Component::CurDir => {}
+			Component::ParentDir if depth > 0 => {
+				start.pop();
+				depth -= 1;
+			}
+			Component::ParentDir => return Err(escapes()),
+			// Rooted but not absolute, like `\foo` on windows
+			Component::RootDir | Component::Prefix(_) => return Err(rooted()),
+		}
+	}
+	Ok(start)
+}
+
+/// Check that name can be stored as a single node
+fn check_name(name: &OsStr) -> Result<(), StorageError> {
+	let bytes = name.as_encoded_bytes();
+	let reason = if bytes.is_empty() {
+		InvalidComponentReason::Empty
+	} else if bytes.contains(&0) {
+		InvalidComponentReason::ContainsNul
+	} else if bytes.iter().any(|b| std::path::is_separator(char::from(*b))) {
+		InvalidComponentReason::ContainsSeparator
+	} else if name == "." || name == ".." {
+		InvalidComponentReason::Reserved
+	} else {
+		return Ok(());
+	};
+	Err(StorageError::InvalidComponent {
+		component: name.to_os_string(),
+		reason,
+	})
+}
+
+/// Check every name in a resolved path with `check_name`
+fn check_names(path: &Path) -> Result<(), StorageError> {
+	for item in path.components() {
+		if let Component::Normal(name) = item {
+			check_name(name)?;
+		}
+	}
+	Ok(())
+}
+
+/// Version of name with ASCII letters lowercased, to compare ignoring case
+#[cfg(not(feature = "unicode-case"))]
+fn fold_case(name: &OsStr) -> OsString {
+	name.to_ascii_lowercase()
+}
+
+/// Lowercase version of name to compare ignoring case, only ASCII letters are lowered in names
+/// that are not UTF-8
+#[cfg(feature = "unicode-case")]
+fn fold_case(name: &OsStr) -> OsString {
+	match name.to_str() {
+		Some(name) => OsString::from(name.to_lowercase()),
+		None => name.to_ascii_lowercase(),
+	}
+}
+
+impl<T> Drop for PathNode<T> {
+	/// Frees children one level at a time, dropping them recursively could overflow the stack on deep trees
+	fn drop(&mut self) {
+		let mut stack: Vec<_> = mem::take(&mut self.items).into_values().collect();
+		while let Some(child) = stack.pop() {
+			// Children still referenced elsewhere are left for whoever drops them last
+			if let Ok(child) = Arc::try_unwrap(child) {
+				let mut child = child.into_inner().unwrap_or_else(PoisonError::into_inner);
+				stack.extend(mem::take(&mut child.items).into_values());
+			}
+		}
+	}
+}
+
+/// What happened when adding a path with `PathStore::add_path_detailed`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AddOutcome {
+	/// The path was not in the store, new_nodes counts it and any parents that were also created
+	Inserted { new_nodes: usize },
+	/// The path was already in the store and its data was replaced
+	Updated,
+	/// The path was already in the store and no data was given
+	Unchanged,
+}
+
+impl From<AddOutcome> for bool {
+	/// True if the path was not already in the store, like `PathStore::add_path` returns
+	fn from(outcome: AddOutcome) -> bool {
+		matches!(outcome, AddOutcome::Inserted { .. })
+	}
+}
+
+/// What `PathStore::visit` should do after visiting a node
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VisitControl {
+	/// Carry on into the children of the node
+	Continue,
+	/// Don't visit anything under the node, but carry on with its siblings
+	SkipSubtree,
+	/// Stop the whole traversal
+	Stop,
 }
 
+/// Tree of absolute paths, each of which can have data attached
+///
+/// Children are always kept in sorted order, so walks and iterators give the same output
+/// no matter what order paths were added in. The store can be shared between threads when `T`
+/// is `Send + Sync`
+///
+/// Every method normalizes its paths the same way before using them, `.` is dropped and `..`
+/// removes the component before it, so `/a/b/../c` and `/a/c` are the same path. This is done
+/// without touching the filesystem. Glob patterns are the exception, `glob` can only resolve `..`
+/// in the part of a pattern before its first wildcard
 pub struct PathStore<T> {
 	root: PathNodeRef<T>,
 	size: usize,
+	/// Relative paths are resolved against this, if set
+	base: Option<PathBuf>,
+	/// Names that only differ in case are the same node
+	case_insensitive: bool,
+	/// Paths are relative to the root rather than absolute
+	relative: bool,
 }
 
 impl<T> PathStore<T> {
 	pub fn new(data: Option<T>) -> Self {
 		Self {
-			root: Rc::new(RwLock::new(PathNode::new(data))),
+			root: Arc::new(RwLock::new(PathNode::new(data))),
 			size: 0,
+			base: None,
+			case_insensitive: false,
+			relative: false,
+		}
+	}
+
+	/// Creates a store for relative paths like `foo/bar`, with the root standing for the empty path
+	///
+	/// Useful for things like the entries of an archive that have no absolute location. Absolute
+	/// paths are a `PathNotRelative` error, and walks return relative paths with the root as an
+	/// empty path. Glob patterns have no leading `/` either
+	pub fn new_relative(data: Option<T>) -> Self {
+		Self {
+			relative: true,
+			..Self::new(data)
 		}
 	}
 
+	/// Creates an empty store where names that only differ in case refer to the same node, like on
+	/// Windows or macOS filesystems
+	///
+	/// A node keeps the case of the name it was first added with, and that is what walks return.
+	/// Only ASCII letters are folded, so `Foo` and `FOO` are the same name but `Ä` and `ä` are not.
+	/// With the `unicode-case` feature UTF-8 names are lowercased with `str::to_lowercase` instead,
+	/// which is close to but not the same as what any particular filesystem does, for example `ß`
+	/// and `SS` are still different names. Names that are not valid UTF-8 always only have their
+	/// ASCII letters folded. Each node keeps an
+	/// index of its children's lowercased names, so lookups stay O(log n) at the cost of storing
+	/// every name twice. Glob patterns and ignore sets still match case sensitively
+	pub fn new_case_insensitive(data: Option<T>) -> Self {
+		Self {
+			case_insensitive: true,
+			..Self::new(data)
+		}
+	}
+
+	/// Creates an empty store that also accepts paths relative to base
+	///
+	/// Relative paths are joined onto base before use, and may not go above it with `..`.
+	/// Absolute paths are used as they are. The base must be absolute
+	pub fn with_base<B: Into<PathBuf>>(base: B) -> Result<Self, StorageError> {
+		let base = base.into();
+		if !base.is_absolute() {
+			return Err(StorageError::PathNotAbsolute { path: base });
+		}
+		Ok(Self {
+			base: Some(base),
+			..Self::new(None)
+		})
+	}
+
 	/// Add path, returns true if it was not already in the store
 	///
-	/// The added path must be absolute
+	/// Some data replaces whatever was stored at path, None leaves existing data alone. Use
+	/// `take_data` to clear it. The added path must be absolute. `..` is resolved lexically so
+	/// `/a/b/../c` adds `/a/c`, which means `..` can never be stored as a name, and going above
+	/// the root is a `PathEscapesRoot` error. Repeated and trailing separators are ignored, so no
+	/// name is ever empty. Adding `/` only sets the data of the root, which is always there
 	pub fn add_path<P: AsRef<Path>>(&mut self, path: P, data: Option<T>) -> Result<bool, StorageError> {
-		if !path.as_ref().is_absolute() {
-			return Err(StorageError::PathNotRelative);
+		self.add_path_detailed(path, data).map(bool::from)
+	}
+
+	/// Same as `add_path`, but tells apart new paths, updated data and nothing happening
+	pub fn add_path_detailed<P: AsRef<Path>>(&mut self, path: P, data: Option<T>) -> Result<AddOutcome, StorageError> {
+		let (node, new_nodes) = self.create_path(path.as_ref())?;
+		if data.is_some() {
+			node.write().unwrap().set_data(data);
+			if new_nodes == 0 {
+				return Ok(AddOutcome::Updated);
+			}
+		}
+
+		if new_nodes == 0 {
+			Ok(AddOutcome::Unchanged)
+		} else {
+			Ok(AddOutcome::Inserted { new_nodes })
+		}
+	}
+
+	/// Add every path without data, returning the result of each `add_path` in the same order
+	///
+	/// A path that fails, such as a relative path without a base, doesn't stop the rest from being
+	/// added. `size` only grows by the nodes that were actually created
+	pub fn add_paths<I, P>(&mut self, paths: I) -> Vec<Result<bool, StorageError>>
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<Path>,
+	{
+		paths.into_iter().map(|path| self.add_path(path, None)).collect()
+	}
+
+	/// Same as `add_paths` with data for each path
+	pub fn add_paths_with_data<I, P>(&mut self, paths: I) -> Vec<Result<bool, StorageError>>
+	where
+		I: IntoIterator<Item = (P, Option<T>)>,
+		P: AsRef<Path>,
+	{
+		paths.into_iter().map(|(path, data)| self.add_path(path, data)).collect()
+	}
+
+	/// Same as `add_path` but for keys like `a.b.c` or `crate::module::Type` that are split on
+	/// separator instead of being filesystem paths
+	///
+	/// Each part between separators is a name below the root, so `a.b.c` with `.` ends up at the
+	/// same node as `/a/b/c`. Empty parts are skipped. Every other part has to be a name `add_path`
+	/// could store, so a part containing `/` or that is `.` or `..` is an `InvalidComponent` error
+	/// and nothing is added. Returns true if the key was not already in the store. Use `walk_str`
+	/// to get keys back out
+	pub fn add_str(&mut self, key: &str, separator: &str, data: Option<T>) -> Result<bool, StorageError> {
+		let names: Vec<_> = key.split(separator).filter(|name| !name.is_empty()).map(OsStr::new).collect();
+		for name in &names {
+			check_name(name)?;
+		}
+		let (node, new_nodes) = self.create_names(names);
+		if data.is_some() {
+			node.write().unwrap().set_data(data);
+		}
+		Ok(new_nodes > 0)
+	}
+
+	/// Store data at path, creating it if needed, and return the data it replaced
+	///
+	/// Creates the same nodes as `add_path`, but like `HashMap::insert` hands back the old data
+	/// rather than dropping it. Returns `Ok(None)` both for new paths and for existing paths that
+	/// had no data. The path must be absolute
+	pub fn insert<P: AsRef<Path>>(&mut self, path: P, data: T) -> Result<Option<T>, StorageError> {
+		let (node, _) = self.create_path(path.as_ref())?;
+		let old = node.write().unwrap().data.replace(data);
+		Ok(old)
+	}
+
+	/// Get an entry for in place manipulation of the data at path
+	///
+	/// The path and any missing parents are created straight away, so `size` already counts them
+	/// even if no data is inserted through the entry. The path must be absolute
+	pub fn entry<P: AsRef<Path>>(&mut self, path: P) -> Result<Entry<'_, T>, StorageError> {
+		let (node, _) = self.create_path(path.as_ref())?;
+		Ok(Entry::new(node))
+	}
+
+	/// Sets the data at path to the result of f if it has none, creating the path if needed
+	///
+	/// Saves looking the path up twice with `contains` then `add_path`. f is only called if there
+	/// is no data yet. The path must be absolute
+	pub fn get_or_insert_with<P: AsRef<Path>, F: FnOnce() -> T>(&mut self, path: P, f: F) -> Result<OccupiedEntry<'_, T>, StorageError> {
+		let (node, _) = self.create_path(path.as_ref())?;
+		{
+			let mut node = node.write().unwrap();
+			if node.data.is_none() {
+				node.data = Some(f());
+			}
 		}
+		Ok(OccupiedEntry::new(node))
+	}
+
+	/// Find the node at path, creating it and any missing parents
+	///
+	/// Also returns the number of nodes that had to be created
+	fn create_path(&mut self, path: &Path) -> Result<(PathNodeRef<T>, usize), StorageError> {
+		let path = self.resolve(path)?;
+		check_names(&path)?;
+		Ok(self.create_names(node_names(&path).iter().map(AsRef::as_ref)))
+	}
 
-		let mut comp = path.as_ref().components().skip(1); // Skip the root path
+	/// Same as `create_path` but following names down from the root
+	fn create_names<'a, I: IntoIterator<Item = &'a OsStr>>(&mut self, names: I) -> (PathNodeRef<T>, usize) {
 		let mut current_in_tree = self.root.clone();
 
-		let mut changed = false;
+		let mut new_nodes = 0;
+		// Last node that was already there and every node created under it, to count once done
+		let mut created = Vec::new();
+		let mut existing = None;
 
-		while let Some(item) = comp.next() {
-			let mut current_tree_lock = current_in_tree
+		for item in names {
+			let current_tree_lock = current_in_tree
 				.read()
 				.expect("Failed to lock tree node when adding path");
-			if let Some(c) = current_tree_lock.items.get(item.as_os_str()) {
+			if let Some((_, c)) = current_tree_lock.child(item, self.case_insensitive) {
 				let c = c.clone();
 				drop(current_tree_lock);
 				current_in_tree = c.clone();
 			} else {
 				self.size += 1;
-				changed = true;
-				let to_add = Rc::new(RwLock::new(PathNode::new(None)));
+				new_nodes += 1;
+				let to_add = PathNode::with_parent(None, &current_in_tree);
+				if existing.is_none() {
+					existing = Some(current_in_tree.clone());
+				}
+				created.push(to_add.clone());
 
 				drop(current_tree_lock);
 				{
 					let mut current_write_lock = current_in_tree.write().unwrap();
-					current_write_lock
-						.items
-						.insert(item.as_os_str().to_os_string(), to_add.clone());
+					current_write_lock.insert_child(item.to_os_string(), to_add.clone(), self.case_insensitive);
 				}
 				current_in_tree = to_add;
 			}
 		}
-		current_in_tree.write().unwrap().set_data(data);
-		Ok(changed)
+		for (i, node) in created.iter().enumerate() {
+			node.write().unwrap().descendants = new_nodes - 1 - i;
+		}
+		if let Some(existing) = existing {
+			PathNode::add_descendants(&existing, new_nodes);
+		}
+		(current_in_tree, new_nodes)
 	}
 
-	pub fn walk(&self) -> Vec<OsString> {
-		let mut out = Vec::new();
-		Self::walk_inner(&self.root, &"/".to_owned().into(), &mut PathBuf::new(), &mut out);
-		out
+	/// Remove the data stored at path and return it
+	///
+	/// If the node is left with no children it is removed from the tree, along with any ancestors that
+	/// are left with neither data nor children. The removed path must be absolute
+	pub fn remove_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Option<T>, StorageError> {
+		let path = self.resolve(path.as_ref())?;
+		let names = node_names(&path);
+		let mut chain = vec![self.root.clone()];
+		// Names the nodes in chain are stored under, which ignoring case might not be the same as names
+		let mut keys = Vec::new();
+
+		for name in names.iter() {
+			let next = match chain
+				.last()
+				.unwrap()
+				.read()
+				.expect("Failed to lock tree node when removing path")
+				.child(name, self.case_insensitive)
+			{
+				Some((key, c)) => {
+					keys.push(key.clone());
+					c.clone()
+				}
+				None => return Ok(None),
+			};
+			chain.push(next);
+		}
+
+		let data = chain.last().unwrap().write().unwrap().data.take();
+
+		// Prune nodes that are now empty, never the root
+		let old_size = self.size;
+		while chain.len() > 1 {
+			let is_empty = {
+				let node = chain
+					.last()
+					.unwrap()
+					.read()
+					.expect("Failed to lock tree node when removing path");
+				node.items.is_empty() && node.data.is_none()
+			};
+			if !is_empty {
+				break;
+			}
+			chain.pop();
+			chain.last().unwrap().write().unwrap().remove_child(&keys[chain.len() - 1]);
+			self.size -= 1;
+		}
+		if self.size != old_size {
+			PathNode::remove_descendants(chain.last().unwrap(), old_size - self.size);
+		}
+		Ok(data)
+	}
+
+	/// Remove the node at prefix and everything under it, returns the number of nodes removed
+	///
+	/// Removing `/` empties the store but keeps the root and its data. The prefix must be absolute
+	pub fn remove_subtree<P: AsRef<Path>>(&mut self, prefix: P) -> Result<usize, StorageError> {
+		let prefix = self.resolve(prefix.as_ref())?;
+		if node_names(&prefix).is_empty() {
+			let mut root = self.root.write().unwrap();
+			root.items.clear();
+			root.folded.clear();
+			root.descendants = 0;
+			return Ok(mem::take(&mut self.size));
+		}
+		let old_size = self.size;
+		self.detach(&prefix);
+		Ok(old_size - self.size)
+	}
+
+	/// Remove the node at prefix and everything under it, returns the path and data of every removed node with data
+	///
+	/// Unlike `remove_subtree` the data of prefix itself is included, so removing `/` also takes
+	/// the data of the root, which is kept. Non-absolute or missing prefixes remove nothing
+	pub fn remove_prefix<P: AsRef<Path>>(&mut self, prefix: P) -> Vec<(PathBuf, T)> {
+		let prefix = match self.resolve(prefix.as_ref()) {
+			Ok(prefix) => prefix,
+			Err(_) => return Vec::new(),
+		};
+		if node_names(&prefix).is_empty() {
+			self.size = 0;
+			{
+				let mut root = self.root.write().unwrap();
+				root.folded.clear();
+				root.descendants = 0;
+			}
+			return IntoIter::new(self.root_path(), self.root.clone()).collect();
+		}
+		let stored = self.stored_path(&prefix);
+		match self.detach(&prefix) {
+			Some(node) => IntoIter::new(stored, node).collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Change the last component of path to new_name, keeping the data and everything under it
+	///
+	/// Nothing is copied, the node is only moved to a new key in its parent so `size` doesn't
+	/// change. Returns `Ok(false)` if path is not in the store or is `/`. A sibling already
+	/// called new_name is a `TargetExists` error, new_name must be a name `add_path` could store
+	pub fn rename<P: AsRef<Path>, Q: AsRef<OsStr>>(&mut self, path: P, new_name: Q) -> Result<bool, StorageError> {
+		let new_name = new_name.as_ref();
+		check_name(new_name)?;
+
+		let path = self.resolve(path.as_ref())?;
+		let mut names = node_names(&path);
+		let name = match names.pop() {
+			Some(name) => name,
+			None => return Ok(false),
+		};
+		let parent = match self.find_node(names.iter().map(AsRef::as_ref)) {
+			Some(parent) => parent,
+			None => return Ok(false),
+		};
+		let mut parent = parent.write().unwrap();
+		let key = match parent.child(&name, self.case_insensitive) {
+			Some((key, _)) => key.clone(),
+			None => return Ok(false),
+		};
+		// Only the node itself may already match, when ignoring case it can be renamed to a new case
+		if parent.child(new_name, self.case_insensitive).is_some_and(|(other, _)| *other != key) {
+			let mut target = path.into_owned();
+			target.set_file_name(new_name);
+			return Err(StorageError::TargetExists { path: target });
+		}
+		let node = parent.remove_child(&key).expect("Child was just found");
+		parent.insert_child(new_name.to_os_string(), node, self.case_insensitive);
+		Ok(true)
+	}
+
+	/// Move the node at from and everything under it to to, creating any missing parents of to
+	///
+	/// The data and children come along without being copied, and `size` only changes by the
+	/// parents that had to be created. from must be in the store and to must not be, and every name
+	/// in to has to be one `add_path` could store. Moving a node under itself is a
+	/// `MoveIntoDescendant` error, which also covers moving `/`
+	pub fn move_subtree<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> Result<(), StorageError> {
+		let from = self.resolve(from.as_ref())?;
+		let to = self.resolve(to.as_ref())?;
+		check_names(&to)?;
+		let moved = self
+			.get_resolved(&from)
+			.ok_or_else(|| StorageError::PathNotFound { path: from.to_path_buf() })?;
+		if self.get_resolved(&to).is_some() {
+			return Err(StorageError::TargetExists { path: to.into_owned() });
+		}
+
+		// to is under from if the moved node is on the way down to the first missing part of to
+		let mut names = node_names(&to);
+		let mut current = self.root.clone();
+		for name in &names {
+			if Arc::ptr_eq(&current, &moved) {
+				return Err(StorageError::MoveIntoDescendant {
+					from: from.to_path_buf(),
+					to: to.to_path_buf(),
+				});
+			}
+			let next = match current
+				.read()
+				.expect("Failed to lock tree node when looking up path")
+				.child(name, self.case_insensitive)
+			{
+				Some((_, child)) => child.clone(),
+				None => break,
+			};
+			current = next;
+		}
+
+		let name = names.pop().expect("The root is always in the store").into_owned();
+		self.detach(&from);
+		let (parent, _) = self.create_names(names.iter().map(AsRef::as_ref));
+		let count = {
+			let mut moved = moved.write().unwrap();
+			moved.parent = Some(Arc::downgrade(&parent));
+			1 + moved.descendants
+		};
+		self.size += count;
+		parent.write().unwrap().insert_child(name, moved, self.case_insensitive);
+		PathNode::add_descendants(&parent, count);
+		Ok(())
 	}
 
-	fn walk_inner(current_node: &PathNodeRef<T>, name: &OsString, current_dir: &mut PathBuf, out: &mut Vec<OsString>) {
-		let mut current_node = &current_node
+	/// Unlink the node at path from its parent and take it and its descendants off the size
+	///
+	/// Path must be resolved and not the root
+	fn detach(&mut self, path: &Path) -> Option<PathNodeRef<T>> {
+		let mut names = node_names(path);
+		let name = names.pop()?;
+		let parent_ref = self.find_node(names.iter().map(AsRef::as_ref))?;
+		let mut parent = parent_ref.write().unwrap();
+		let key = parent.child(&name, self.case_insensitive)?.0.clone();
+		let removed = parent.remove_child(&key)?;
+		drop(parent);
+		let count = removed
 			.read()
-			.expect("Failed to lock tree node when adding path");
+			.expect("Failed to lock tree node when removing subtree")
+			.descendants + 1;
+		self.size -= count;
+		PathNode::remove_descendants(&parent_ref, count);
+		Some(removed)
+	}
 
-		current_dir.push(name);
-//		current_dir.push(&current_node.name);
+	/// Returns true if there is a node at exactly path, either added directly or as a parent of another path
+	///
+	/// Non-absolute paths are never in the store
+	pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+		self.get_node(path.as_ref()).is_some()
+	}
 
-		if current_node.items.is_empty() {
-			out.push(current_dir.as_os_str().to_owned());
-//			println!("{}", current_dir.display())
-		} else {
-			for item in current_node.items.iter() {
-				Self::walk_inner(item.1, item.0, current_dir, out);
+	/// Same as `contains`, for callers that want the name to say what is being checked
+	pub fn contains_path<P: AsRef<Path>>(&self, path: P) -> bool {
+		self.contains(path)
+	}
+
+	/// Returns true if some path in the store is prefix or starts with it, whether or not anything has data
+	///
+	/// Every parent of a stored path is a node, so this is the same check as `contains` and only
+	/// follows prefix down from the root. Prefixes match whole components, `/f` is not a prefix of
+	/// `/foo`. Pair with `count_under` to find how many nodes are below it
+	pub fn contains_prefix<P: AsRef<Path>>(&self, prefix: P) -> bool {
+		self.contains(prefix)
+	}
+
+	/// Add every path in other to this store, other's data wins where both have data
+	pub fn merge(&mut self, other: PathStore<T>) {
+		self.merge_with(other, |_, incoming| incoming)
+	}
+
+	/// Add every path in other to this store, calling resolve(existing, incoming) where both have data
+	///
+	/// Subtrees missing from this store are moved over as they are. Other's base is not carried over
+	pub fn merge_with<F: FnMut(T, T) -> T>(&mut self, other: PathStore<T>, mut resolve: F) {
+		let refold = other.case_insensitive != self.case_insensitive;
+		let mut stack = vec![(self.root.clone(), other.root)];
+		// Nodes that had subtrees moved under them and how many nodes, counted once nothing is locked
+		let mut grown = Vec::new();
+
+		while let Some((existing_ref, incoming)) = stack.pop() {
+			let (items, data) = {
+				let mut incoming = incoming.write().unwrap();
+				(mem::take(&mut incoming.items), incoming.data.take())
+			};
+
+			let mut existing = existing_ref.write().unwrap();
+			existing.data = match (existing.data.take(), data) {
+				(Some(a), Some(b)) => Some(resolve(a, b)),
+				(a, b) => a.or(b),
+			};
+
+			for (name, child) in items {
+				match existing.child(&name, self.case_insensitive) {
+					Some((_, c)) => stack.push((c.clone(), child)),
+					None => {
+						let count = {
+							let mut child = child.write().unwrap();
+							child.parent = Some(Arc::downgrade(&existing_ref));
+							1 + child.descendants
+						};
+						self.size += count;
+						grown.push((existing_ref.clone(), count));
+						if refold {
+							PathNode::refold(&child, self.case_insensitive);
+						}
+						existing.insert_child(name, child, self.case_insensitive);
+					}
+				}
 			}
 		}
+		for (node, count) in grown {
+			PathNode::add_descendants(&node, count);
+		}
+	}
 
-		current_dir.pop();
+	/// Remove every branch that has no data anywhere in it
+	///
+	/// Nodes without data are kept as long as something under them has data. The root is never removed
+	pub fn prune(&mut self) {
+		let removed = self.root.write().unwrap().prune();
+		self.size -= removed;
 	}
 
-	pub fn size(&self) -> usize {
-		self.size
+	/// Remove every node predicate rejects, along with everything under it
+	///
+	/// Predicate is called on every node other than the root, parents before their children, with
+	/// the node's data if it has any. The descendants of a rejected node are removed with it
+	/// without predicate seeing them, they are never moved up to take its place
+	pub fn retain<F: FnMut(&Path, Option<&T>) -> bool>(&mut self, mut predicate: F) {
+		let mut stack = vec![(self.root_path(), self.root.clone())];
+		let mut removed = 0;
+
+		while let Some((path, node)) = stack.pop() {
+			let mut node = node.write().unwrap();
+			node.items.retain(|name, child| {
+				let path = path.join(name);
+				let keep = {
+					let child = child.read().expect("Failed to lock tree node when filtering tree");
+					let keep = predicate(&path, child.data.as_ref());
+					if !keep {
+						removed += 1 + child.descendants;
+					}
+					keep
+				};
+				if keep {
+					stack.push((path, child.clone()));
+				}
+				keep
+			});
+			node.retain_folded();
+		}
+		self.size -= removed;
+		PathNode::recount(&self.root);
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use super::PathStore;
-	use std::ffi::OsString;
+	/// Returns true if there is a node at exactly path and it has data stored
+	///
+	/// Unlike `contains` this is false for parents that were only created implicitly by `add_path`,
+	/// the root counts if it was given data in `PathStore::new`
+	pub fn contains_entry<P: AsRef<Path>>(&self, path: P) -> bool {
+		self.get_node(path.as_ref()).is_some_and(|node| {
+			node.read()
+				.expect("Failed to lock tree node when looking up path")
+				.data
+				.is_some()
+		})
+	}
 
-	#[test]
-	fn root_store_push() {
-		let mut store = PathStore::new(None::<()>);
-		assert_eq!(store.size, 0);
+	/// Calls f with the data stored at path and returns its result
+	///
+	/// None if the path is not in the store or no data was stored there, in which case f is not called.
+	/// The node is locked for reading while f runs
+	pub fn get_with<P: AsRef<Path>, R, F: FnOnce(&T) -> R>(&self, path: P, f: F) -> Option<R> {
+		self.get_node(path.as_ref())?
+			.read()
+			.expect("Failed to lock tree node when getting data")
+			.data
+			.as_ref()
+			.map(f)
+	}
 
-		assert_eq!(store.add_path("/f", None), Ok(true));
-		assert_eq!(store.add_path("/g", None), Ok(true));
-		assert_eq!(store.add_path("/f", None), Ok(false));
-		assert_eq!(store.add_path("h", None).is_err(), true);
-		assert_eq!(store.size, 2);
+	/// Returns a clone of the data stored at path
+	///
+	/// None if the path is not in the store or no data was stored there
+	pub fn get_cloned<P: AsRef<Path>>(&self, path: P) -> Option<T>
+	where
+		T: Clone,
+	{
+		self.get_with(path, T::clone)
 	}
 
-	#[test]
-	fn root_store_push_double() {
-		let mut store = PathStore::new(None::<()>);
-		assert_eq!(store.size, 0);
+	/// Calls f with mutable access to the data stored at path and returns its result
+	///
+	/// The data can be changed in place, replaced or cleared. No nodes are created, so a path
+	/// not in the store is a `PathNotFound` error
+	pub fn with_data_mut<P: AsRef<Path>, R, F: FnOnce(&mut Option<T>) -> R>(&mut self, path: P, f: F) -> Result<R, StorageError> {
+		let path = self.resolve(path.as_ref())?;
+		let node = self
+			.get_resolved(&path)
+			.ok_or_else(|| StorageError::PathNotFound { path: path.to_path_buf() })?;
+		let mut node = node.write().unwrap();
+		Ok(f(&mut node.data))
+	}
 
-		assert_eq!(store.add_path("/f", None), Ok(true));
-		assert_eq!(store.add_path("/g", None), Ok(true));
-		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
-		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
-		assert_eq!(store.add_path("/f", None), Ok(false));
-		assert_eq!(store.add_path("h", None).is_err(), true);
-		assert_eq!(store.size, 5);
+	/// Set the data of a path already in the store, returns false if it is not in the store
+	///
+	/// Unlike `add_path` no nodes are created, and None clears the data
+	pub fn set_data<P: AsRef<Path>>(&mut self, path: P, data: Option<T>) -> Result<bool, StorageError> {
+		match self.with_data_mut(path, |d| *d = data) {
+			Ok(()) => Ok(true),
+			Err(StorageError::PathNotFound { .. }) => Ok(false),
+			Err(e) => Err(e),
+		}
+	}
 
-		let walk = store.walk();
-		assert_eq!(walk, vec![
-			OsString::from("/f/FDrive/hello".to_owned()),
-			OsString::from("/f/FDrive/files".to_owned()),
-			OsString::from("/g".to_owned()),
-		]);
+	/// Swap in data at a path already in the store, returning what was there before
+	///
+	/// Nothing is stored if the path is not in the store
+	pub fn replace_data<P: AsRef<Path>>(&mut self, path: P, data: T) -> Option<T> {
+		self.get_node(path.as_ref())?.write().unwrap().data.replace(data)
+	}
+
+	/// Take the data stored at path, leaving the node and its children in place
+	///
+	/// Also works on the root, giving back the data passed to `PathStore::new`
+	pub fn take_data<P: AsRef<Path>>(&mut self, path: P) -> Option<T> {
+		self.get_node(path.as_ref())?.write().unwrap().data.take()
+	}
+
+	/// Returns how many components path is below the root, which is at depth 0
+	///
+	/// None if the path is not in the store
+	pub fn depth<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+		let path = self.resolve(path.as_ref()).ok()?;
+		self.get_resolved(&path)?;
+		Some(node_names(&path).len())
+	}
+
+	/// Returns the depth of the deepest node, 0 if there is only the root
+	pub fn max_depth(&self) -> usize {
+		let mut max_depth = 0;
+		let mut stack = vec![(0, self.root.clone())];
+		while let Some((depth, node)) = stack.pop() {
+			max_depth = max_depth.max(depth);
+			stack.extend(
+				node.read()
+					.expect("Failed to lock tree node when measuring depth")
+					.items
+					.values()
+					.map(|child| (depth + 1, child.clone())),
+			);
+		}
+		max_depth
+	}
+
+	/// Returns the number of nodes below prefix, not counting prefix itself
+	///
+	/// Every node keeps a count of the nodes below it, so this only follows prefix down from the
+	/// root and takes O(depth) time. Counting under `/` gives `size`, a prefix that is not in the
+	/// store gives 0
+	pub fn count_under<P: AsRef<Path>>(&self, prefix: P) -> usize {
+		self.get_node(prefix.as_ref()).map_or(0, |node| {
+			node.read()
+				.expect("Failed to lock tree node when counting nodes")
+				.descendants
+		})
+	}
+
+	/// Same as `count_under` but only counts nodes that have data
+	///
+	/// Only the number of nodes is cached, so this visits every node under prefix and takes O(subtree) time
+	pub fn count_data_under<P: AsRef<Path>>(&self, prefix: P) -> usize {
+		self.get_node(prefix.as_ref()).map_or(0, |node| {
+			node.read()
+				.expect("Failed to lock tree node when counting nodes")
+				.count_descendants_where(|node| node.data.is_some())
+		})
+	}
+
+	/// Returns the deepest path with data that is path itself or one of its parents
+	///
+	/// The full path doesn't have to be in the store, with `/a` and `/a/b/c` stored `/a/b/c/d/e`
+	/// gives `/a/b/c`. The root counts if it has data. None if no such path has data
+	pub fn longest_prefix<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+		let path = self.resolve(path.as_ref()).ok()?;
+		let mut longest = None;
+		self.descend(&path, |path, node| {
+			if node.data.is_some() {
+				longest = Some(path.to_path_buf());
+			}
+		});
+		longest
+	}
+
+	/// Same as `longest_prefix` but also returns a copy of the data found there
+	///
+	/// Useful when data is configuration that applies to everything under a directory, each path
+	/// gets the data of its nearest ancestor that has some, or its own if it has data
+	pub fn ancestor_data<P: AsRef<Path>>(&self, path: P) -> Option<(PathBuf, T)>
+	where
+		T: Clone,
+	{
+		let path = self.resolve(path.as_ref()).ok()?;
+		let mut nearest = None;
+		self.descend(&path, |path, node| {
+			if let Some(data) = &node.data {
+				nearest = Some((path.to_path_buf(), data.clone()));
+			}
+		});
+		nearest
+	}
+
+	/// Returns the deepest path in the store that is path itself or one of its parents, and how many
+	/// components of path it matched
+	///
+	/// With only `/f/FDrive/files` stored `/f/FDrive/files/deep/deeper` gives `/f/FDrive/files` and 3,
+	/// so the last 2 components are the ones missing. Data is ignored. At worst this is `/` and 0,
+	/// which is also the result for paths that can't be in the store like non-absolute ones
+	pub fn longest_existing_prefix<P: AsRef<Path>>(&self, path: P) -> (PathBuf, usize) {
+		match self.resolve(path.as_ref()) {
+			Ok(path) => self.descend(&path, |_, _| {}),
+			Err(_) => (self.root_path(), 0),
+		}
+	}
+
+	/// The path of the deepest node on path, with names as they are stored rather than as they are in path
+	///
+	/// Path must be resolved. Only differs from the components of path when ignoring case
+	fn stored_path(&self, path: &Path) -> PathBuf {
+		self.descend(path, |_, _| {}).0
+	}
+
+	/// Follow path down from the root for as long as it is in the store, calling f on each node
+	/// starting with the root
+	///
+	/// Returns the path of the last node reached and the number of components matched, path must be resolved
+	fn descend<F: FnMut(&Path, &PathNode<T>)>(&self, path: &Path, mut f: F) -> (PathBuf, usize) {
+		let mut current_in_tree = self.root.clone();
+		let mut current_path = self.root_path();
+		let mut matched = 0;
+
+		for name in node_names(path) {
+			let next = {
+				let node = current_in_tree
+					.read()
+					.expect("Failed to lock tree node when looking up path");
+				f(&current_path, &node);
+				match node.child(&name, self.case_insensitive) {
+					Some((key, child)) => {
+						current_path.push(key);
+						child.clone()
+					}
+					None => return (current_path, matched),
+				}
+			};
+			matched += 1;
+			current_in_tree = next;
+		}
+		f(&current_path, &current_in_tree.read().expect("Failed to lock tree node when looking up path"));
+		(current_path, matched)
+	}
+
+	/// Returns the names of the nodes directly under path, in sorted order
+	///
+	/// None if the path is not in the store, an empty Vec if it is a leaf
+	pub fn children<P: AsRef<Path>>(&self, path: P) -> Option<Vec<OsString>> {
+		Some(
+			self.get_node(path.as_ref())?
+				.read()
+				.expect("Failed to lock tree node when listing children")
+				.items
+				.keys()
+				.cloned()
+				.collect(),
+		)
+	}
+
+	/// Same as `children` but each name comes with whether that child has children of its own
+	///
+	/// Lets a tree view show which entries can be expanded without listing them first
+	pub fn children_expandable<P: AsRef<Path>>(&self, path: P) -> Option<Vec<(OsString, bool)>> {
+		Some(
+			self.get_node(path.as_ref())?
+				.read()
+				.expect("Failed to lock tree node when listing children")
+				.items
+				.iter()
+				.map(|(name, child)| {
+					let child = child.read().expect("Failed to lock tree node when listing children");
+					(name.clone(), !child.items.is_empty())
+				})
+				.collect(),
+		)
+	}
+
+	/// Returns the full paths of the children that could complete the last component of partial
+	///
+	/// The input is split into a directory and a partial name, `/f/FD` lists the children of `/f`
+	/// whose names start with `FD`. Names are compared byte-wise, ignoring case if the store does.
+	/// Input ending in a separator lists every child of that directory, a directory that is not in
+	/// the store gives an empty Vec
+	pub fn complete<P: AsRef<Path>>(&self, partial: P) -> Vec<PathBuf> {
+		let partial = partial.as_ref();
+		let ends_in_separator = partial
+			.as_os_str()
+			.as_encoded_bytes()
+			.last()
+			.is_some_and(|b| std::path::is_separator(*b as char));
+		let (dir, name) = match (partial.parent(), partial.file_name()) {
+			(Some(dir), Some(name)) if !ends_in_separator => (dir, name),
+			_ => (partial, OsStr::new("")),
+		};
+
+		let dir = match self.resolve(dir) {
+			Ok(dir) => dir,
+			Err(_) => return Vec::new(),
+		};
+		let node = match self.get_resolved(&dir) {
+			Some(node) => node,
+			None => return Vec::new(),
+		};
+		let dir = self.stored_path(&dir);
+
+		// Names starting with a prefix sort together right after it, so only those are visited
+		fn starting_with<'a, V>(names: &'a BTreeMap<OsString, V>, prefix: &'a OsStr) -> impl Iterator<Item = (&'a OsString, &'a V)> {
+			names
+				.range::<OsStr, _>((Bound::Included(prefix), Bound::Unbounded))
+				.take_while(move |(name, _)| name.as_encoded_bytes().starts_with(prefix.as_encoded_bytes()))
+		}
+
+		let node = node.read().expect("Failed to lock tree node when completing path");
+		if self.case_insensitive {
+			let folded = fold_case(name);
+			let mut out: Vec<_> = starting_with(&node.folded, &folded).map(|(_, child)| dir.join(child)).collect();
+			// Back into the order of the stored names
+			out.sort();
+			out
+		} else {
+			starting_with(&node.items, name).map(|(child, _)| dir.join(child)).collect()
+		}
+	}
+
+	/// Returns the deepest path that every stored path is under
+	///
+	/// Follows the tree down from the root for as long as the node has exactly one child and no
+	/// data of its own. An empty store gives `/`, a store with only `/a/b/c` gives `/a/b/c`
+	pub fn common_prefix(&self) -> PathBuf {
+		let mut current_in_tree = self.root.clone();
+		let mut path = self.root_path();
+		loop {
+			let next = {
+				let node = current_in_tree
+					.read()
+					.expect("Failed to lock tree node when finding common prefix");
+				if node.items.len() != 1 || node.data.is_some() {
+					break;
+				}
+				let (name, child) = node.items.iter().next().unwrap();
+				path.push(name);
+				child.clone()
+			};
+			current_in_tree = next;
+		}
+		path
+	}
+
+	/// Lazily iterate over the path of every node above path, nearest first and ending with the root
+	///
+	/// Path itself is not included, so the root has no ancestors. None if the path is not in the store.
+	/// The chain follows each node's parent link, if a parent has already been dropped by another
+	/// thread removing it the chain stops there
+	pub fn ancestors<P: AsRef<Path>>(&self, path: P) -> Option<Ancestors<'_, T>> {
+		let path = self.resolve(path.as_ref()).ok()?;
+		let node = self.get_resolved(&path)?;
+		Some(Ancestors::new(self.stored_path(&path), node))
+	}
+
+	/// Returns the path of the node above path, found through its parent link
+	///
+	/// None for the root or a path that is not in the store
+	pub fn parent_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+		self.ancestors(path)?.next()
+	}
+
+	/// Returns whether the node at path has no children, None if the path is not in the store
+	pub fn is_leaf<P: AsRef<Path>>(&self, path: P) -> Option<bool> {
+		Some(
+			self.get_node(path.as_ref())?
+				.read()
+				.expect("Failed to lock tree node when looking up path")
+				.items
+				.is_empty(),
+		)
+	}
+
+	/// Copy the node at path and everything under it into a new store, with that node as the root
+	///
+	/// None if the path is not in the store
+	pub fn subtree<P: AsRef<Path>>(&self, path: P) -> Option<PathStore<T>>
+	where
+		T: Clone,
+	{
+		let node = self.get_node(path.as_ref())?;
+		let node = node.read().expect("Failed to lock tree node when cloning tree");
+		Some(Self {
+			size: node.descendants,
+			root: node.deep_clone(),
+			base: None,
+			case_insensitive: self.case_insensitive,
+			relative: self.relative,
+		})
+	}
+
+	/// Build a store with the same paths where every piece of data is replaced by f of it
+	///
+	/// f is only called on nodes that have data, nodes without data stay without. Consumes the
+	/// store so the data can be moved into f, see `map_ref` to keep it
+	pub fn map<U, F: FnMut(T) -> U>(self, mut f: F) -> PathStore<U> {
+		let root = Arc::new(RwLock::new(PathNode::new(None)));
+		let mut stack = vec![(self.root.clone(), root.clone())];
+
+		while let Some((from, to)) = stack.pop() {
+			let (items, folded, data, descendants) = {
+				let mut from = from.write().unwrap();
+				(mem::take(&mut from.items), mem::take(&mut from.folded), from.data.take(), from.descendants)
+			};
+			let mut to_lock = to.write().unwrap();
+			to_lock.data = data.map(&mut f);
+			to_lock.folded = folded;
+			to_lock.descendants = descendants;
+			for (name, child) in items {
+				let copy = PathNode::with_parent(None, &to);
+				to_lock.items.insert(name, copy.clone());
+				stack.push((child, copy));
+			}
+		}
+
+		PathStore {
+			root,
+			size: self.size,
+			base: self.base,
+			case_insensitive: self.case_insensitive,
+			relative: self.relative,
+		}
+	}
+
+	/// Same as `map` but borrows the store, f is given a reference to each piece of data
+	pub fn map_ref<U, F: FnMut(&T) -> U>(&self, f: F) -> PathStore<U> {
+		PathStore {
+			root: self
+				.root
+				.read()
+				.expect("Failed to lock tree node when cloning tree")
+				.map_copy(f),
+			size: self.size,
+			base: self.base.clone(),
+			case_insensitive: self.case_insensitive,
+			relative: self.relative,
+		}
+	}
+
+	/// Turn path into the absolute path it refers to in the store
+	///
+	/// `..` is resolved without looking at the filesystem and may not go above the root. Relative
+	/// paths are only allowed with a base, and must stay under it
+	fn resolve<'a>(&self, path: &'a Path) -> Result<Cow<'a, Path>, StorageError> {
+		let escapes_root = || StorageError::PathEscapesRoot { path: path.to_path_buf() };
+		let not_absolute = || StorageError::PathNotAbsolute { path: path.to_path_buf() };
+		if self.relative {
+			let not_relative = || StorageError::PathNotRelative { path: path.to_path_buf() };
+			if path.has_root() || path.is_absolute() {
+				return Err(not_relative());
+			}
+			return join_lexically(PathBuf::from("/"), path, escapes_root, not_relative).map(Cow::Owned);
+		}
+		if path.is_absolute() {
+			if !path.components().any(|c| c == Component::ParentDir) {
+				return Ok(Cow::Borrowed(path));
+			}
+			let mut components = path.components();
+			let mut root = PathBuf::new();
+			while let Some(item @ (Component::Prefix(_) | Component::RootDir)) = components.clone().next() {
+				root.push(item);
+				components.next();
+			}
+			return join_lexically(root, components.as_path(), escapes_root, not_absolute).map(Cow::Owned);
+		}
+		let base = self.base.as_ref().ok_or_else(not_absolute)?;
+		let escapes_base = || StorageError::PathEscapesBase { path: path.to_path_buf() };
+		join_lexically(base.clone(), path, escapes_base, not_absolute).map(Cow::Owned)
+	}
+
+	/// Path of the root in walks, `/` or empty in relative stores
+	fn root_path(&self) -> PathBuf {
+		if self.relative {
+			PathBuf::new()
+		} else {
+			PathBuf::from("/")
+		}
+	}
+
+	/// Find the node at path without modifying the tree
+	fn get_node(&self, path: &Path) -> Option<PathNodeRef<T>> {
+		self.get_resolved(&self.resolve(path).ok()?)
+	}
+
+	/// Same as `get_node` for a path that has already been through `resolve`
+	fn get_resolved(&self, path: &Path) -> Option<PathNodeRef<T>> {
+		self.find_node(node_names(path).iter().map(AsRef::as_ref))
+	}
+
+	/// Follow names down from the root
+	fn find_node<'a, I: IntoIterator<Item = &'a OsStr>>(&self, names: I) -> Option<PathNodeRef<T>> {
+		let mut current_in_tree = self.root.clone();
+		for name in names {
+			let next = current_in_tree
+				.read()
+				.expect("Failed to lock tree node when looking up path")
+				.child(name, self.case_insensitive)?
+				.1
+				.clone();
+			current_in_tree = next;
+		}
+		Some(current_in_tree)
+	}
+
+	/// Returns the path of every leaf, and of every other node that has data
+	///
+	/// Children are visited in sorted order, so the output is the same between runs. Paths are
+	/// given in canonical form, `/` followed by the names joined with single separators and no
+	/// trailing separator, so `/a//b/` is walked as `/a/b`. The root is walked as `/`
+	pub fn walk(&self) -> Vec<PathBuf> {
+		self.iter().collect()
+	}
+
+	/// Same as `walk` but each path is joined with separator, the opposite of `add_str`
+	///
+	/// The root is an empty string. Names that are not valid UTF-8 have anything invalid replaced by U+FFFD
+	pub fn walk_str(&self, separator: &str) -> Vec<String> {
+		let mut out = Vec::new();
+		let mut stack = vec![(String::new(), self.root.clone())];
+
+		while let Some((key, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when walking tree");
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				let name = name.to_string_lossy();
+				let child_key = if key.is_empty() {
+					name.into_owned()
+				} else {
+					format!("{}{}{}", key, separator, name)
+				};
+				stack.push((child_key, child.clone()));
+			}
+			if node.items.is_empty() || node.data.is_some() {
+				out.push(key);
+			}
+		}
+		out
+	}
+
+	/// Lazily iterate over the same paths as `walk`, in the same order
+	///
+	/// Only the node being expanded is locked, never the whole tree
+	pub fn iter(&self) -> Paths<'_, T> {
+		Paths::new(self.root_path(), self.root.clone())
+	}
+
+	/// Same as `walk` but only for the subtree under prefix, including prefix itself
+	///
+	/// Paths returned are still absolute. A prefix that is not in the store is a `PathNotFound`
+	/// error, `/` is the same as a full walk
+	pub fn walk_under<P: AsRef<Path>>(&self, prefix: P) -> Result<Vec<PathBuf>, StorageError> {
+		let prefix = self.resolve(prefix.as_ref())?;
+		let node = self
+			.get_resolved(&prefix)
+			.ok_or_else(|| StorageError::PathNotFound { path: prefix.to_path_buf() })?;
+		Ok(Paths::under(self.stored_path(&prefix), node).collect())
+	}
+
+	/// Same as `walk_under` but the paths returned are relative to prefix
+	///
+	/// Walking under `/a/b` gives `c/d.txt` rather than `/a/b/c/d.txt`. If the prefix itself would be
+	/// in the walk it is returned as an empty path, so joining every result onto a new directory
+	/// re-roots the subtree there
+	pub fn walk_relative<P: AsRef<Path>>(&self, prefix: P) -> Result<Vec<PathBuf>, StorageError> {
+		let prefix = self.resolve(prefix.as_ref())?;
+		let node = self
+			.get_resolved(&prefix)
+			.ok_or_else(|| StorageError::PathNotFound { path: prefix.to_path_buf() })?;
+		Ok(Paths::under(PathBuf::new(), node).collect())
+	}
+
+	/// Same as `walk` but only for the paths below prefix, not counting prefix itself
+	///
+	/// Prefixes match whole components, so `/foo/ba` never matches `/foo/bar`. Only the subtree
+	/// of prefix is walked, a prefix that is not in the store gives an empty Vec
+	pub fn paths_under<P: AsRef<Path>>(&self, prefix: P) -> Vec<PathBuf> {
+		let prefix = match self.resolve(prefix.as_ref()) {
+			Ok(prefix) => prefix,
+			Err(_) => return Vec::new(),
+		};
+		let node = match self.get_resolved(&prefix) {
+			Some(node) => node,
+			None => return Vec::new(),
+		};
+		let prefix = self.stored_path(&prefix);
+		Paths::under(prefix.clone(), node).filter(|path| *path != prefix).collect()
+	}
+
+	/// Same as `walk` but stops max_depth components below the root
+	///
+	/// Nodes at max_depth are returned even if they have children, as if they were leaves. A
+	/// max_depth of 0 only returns the root, `usize::MAX` is a full walk
+	pub fn walk_depth(&self, max_depth: usize) -> Vec<PathBuf> {
+		self.iter_depth(max_depth).collect()
+	}
+
+	/// Lazily iterate over the same paths as `walk_depth`, nothing below max_depth is ever locked
+	pub fn iter_depth(&self, max_depth: usize) -> Paths<'_, T> {
+		Paths::with_max_depth(self.root_path(), self.root.clone(), max_depth)
+	}
+
+	/// Returns the path of every node including the root and parents created implicitly
+	///
+	/// Parents always come before their children, so the output can be used to create directories in order
+	pub fn walk_all(&self) -> Vec<PathBuf> {
+		self.iter_all().collect()
+	}
+
+	/// Lazily iterate over the same paths as `walk_all`, in the same order
+	pub fn iter_all(&self) -> AllPaths<'_, T> {
+		AllPaths::new(self.root_path(), self.root.clone())
+	}
+
+	/// Returns the path of every node that has data, whether or not it is a leaf
+	///
+	/// Same paths as `walk_with_data` but without needing to clone the data
+	pub fn walk_data_paths(&self) -> Vec<PathBuf> {
+		self.iter_data_paths().collect()
+	}
+
+	/// Lazily iterate over the same paths as `walk_data_paths`, in the same order
+	pub fn iter_data_paths(&self) -> DataPaths<'_, T> {
+		DataPaths::new(self.root_path(), self.root.clone())
+	}
+
+	/// Depth first traversal calling f on every node, f decides whether to go deeper
+	///
+	/// Parents are visited before their children, in sorted order. Only the node being visited is
+	/// locked, for reading, while f runs
+	pub fn visit<F: FnMut(&Path, Option<&T>) -> VisitControl>(&self, mut f: F) {
+		let mut stack = vec![(self.root_path(), self.root.clone())];
+
+		while let Some((path, node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when visiting tree");
+			match f(&path, node.data.as_ref()) {
+				VisitControl::Continue => {
+					// Reversed so children come off the stack in sorted order
+					for (name, child) in node.items.iter().rev() {
+						stack.push((path.join(name), child.clone()));
+					}
+				}
+				VisitControl::SkipSubtree => {}
+				VisitControl::Stop => return,
+			}
+		}
+	}
+
+	/// Same as `walk` but only goes into nodes that predicate accepts
+	///
+	/// Predicate is called with the path of every node reached other than the root, internal
+	/// nodes included. Returning false for a node leaves out it and everything under it without
+	/// visiting them, so `|p| p.starts_with("/f")` never looks inside `/g`
+	pub fn filter_walk<F: FnMut(&Path) -> bool>(&self, mut predicate: F) -> Vec<PathBuf> {
+		let mut out = Vec::new();
+		let mut stack = vec![(self.root_path(), self.root.clone())];
+
+		while let Some((path, node)) = stack.pop() {
+			if !Arc::ptr_eq(&node, &self.root) && !predicate(&path) {
+				continue;
+			}
+			let node = node.read().expect("Failed to lock tree node when walking tree");
+			// Reversed so children come off the stack in sorted order
+			for (name, child) in node.items.iter().rev() {
+				stack.push((path.join(name), child.clone()));
+			}
+			if node.items.is_empty() || node.data.is_some() {
+				out.push(path);
+			}
+		}
+		out
+	}
+
+	/// Call f on every node in the same order as `walk_all`, with its data if it has any
+	///
+	/// Nothing is collected or cloned, the data is borrowed while its node is locked for reading
+	pub fn for_each<F: FnMut(&Path, Option<&T>)>(&self, mut f: F) {
+		self.visit(|path, data| {
+			f(path, data);
+			VisitControl::Continue
+		});
+	}
+
+	/// Returns the path of every node whose data predicate accepts, in the same order as `walk_all`
+	///
+	/// Only nodes with data are checked. Each node is locked for reading while predicate looks at
+	/// its data, and no other node is locked then
+	pub fn find_where<F: Fn(&T) -> bool>(&self, predicate: F) -> Vec<PathBuf> {
+		let mut out = Vec::new();
+		self.for_each(|path, data| {
+			if data.is_some_and(&predicate) {
+				out.push(path.to_path_buf());
+			}
+		});
+		out
+	}
+
+	/// Returns true if predicate accepts the data of some node, stopping at the first one
+	pub fn any_where<F: Fn(&T) -> bool>(&self, predicate: F) -> bool {
+		let mut found = false;
+		self.visit(|_, data| {
+			if data.is_some_and(&predicate) {
+				found = true;
+				VisitControl::Stop
+			} else {
+				VisitControl::Continue
+			}
+		});
+		found
+	}
+
+	/// Returns true if predicate accepts the data of every node that has data, stopping at the
+	/// first one it rejects
+	///
+	/// True if no node has data
+	pub fn all_where<F: Fn(&T) -> bool>(&self, predicate: F) -> bool {
+		!self.any_where(|data| !predicate(data))
+	}
+
+	/// Lazily iterate over every node with children before their parents, ending with the root
+	///
+	/// Useful for bottom up work like deleting directories
+	pub fn iter_postorder(&self) -> PostOrder<'_, T> {
+		PostOrder::new(self.root_path(), self.root.clone())
+	}
+
+	/// Lazily iterate over every node level by level, along with its depth below the root
+	///
+	/// The root comes first at depth 0, then every node at depth 1 in sorted order, and so on
+	pub fn iter_bfs(&self) -> BreadthFirst<'_, T> {
+		BreadthFirst::new(self.root_path(), self.root.clone())
+	}
+
+	/// Returns the path of every node in the same order as `iter_bfs`, without the depths
+	pub fn walk_bfs(&self) -> Vec<PathBuf> {
+		self.iter_bfs().map(|(_, path)| path).collect()
+	}
+
+	/// Returns every node that has data, paired with a clone of that data
+	///
+	/// Unlike `walk` this includes nodes in the middle of the tree, as long as they carry data
+	pub fn walk_with_data(&self) -> Vec<(PathBuf, T)>
+	where
+		T: Clone,
+	{
+		self.iter_entries().collect()
+	}
+
+	/// Lazily iterate over the same entries as `walk_with_data`, in the same order
+	pub fn iter_entries(&self) -> Entries<'_, T>
+	where
+		T: Clone,
+	{
+		Entries::new(self.root_path(), self.root.clone())
+	}
+
+	/// Returns the number of nodes in the store not counting the root, with or without data
+	///
+	/// Parents that were created implicitly count, so adding `/f/FDrive/files` alone adds 3. Kept as a
+	/// counter so this doesn't walk the tree, see `count_leaves` and `count_data_nodes` for other counts
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	/// Returns the number of nodes without children, counting the root if the store is empty
+	pub fn count_leaves(&self) -> usize {
+		self.count_nodes_where(|node| node.items.is_empty())
+	}
+
+	/// Returns the number of nodes that have data, counting the root if it has data
+	pub fn count_data_nodes(&self) -> usize {
+		self.count_nodes_where(|node| node.data.is_some())
+	}
+
+	/// Number of nodes that match including the root
+	fn count_nodes_where<F: Fn(&PathNode<T>) -> bool>(&self, matches: F) -> usize {
+		let root = self.root.read().expect("Failed to lock tree node when counting nodes");
+		usize::from(matches(&root)) + root.count_descendants_where(matches)
+	}
+
+	/// Returns true if there are no paths below the root, the root may still have data
+	pub fn is_empty(&self) -> bool {
+		self.size == 0
+	}
+
+	/// Remove every path and the root's data, leaving the store as if it was just created
+	///
+	/// The base is kept. Use `clear_keep_root_data` to keep the root's data
+	pub fn clear(&mut self) {
+		self.root = Arc::new(RwLock::new(PathNode::new(None)));
+		self.size = 0;
+	}
+
+	/// Same as `clear` but the new root keeps the data the old root had
+	pub fn clear_keep_root_data(&mut self) {
+		let data = self.root.write().unwrap().data.take();
+		self.root = Arc::new(RwLock::new(PathNode::new(data)));
+		self.size = 0;
+	}
+}
+
+impl<T: Clone> Clone for PathStore<T> {
+	/// Copies every node, so the clone shares nothing with the original
+	fn clone(&self) -> Self {
+		let root = self.root
+			.read()
+			.expect("Failed to lock tree node when cloning tree")
+			.deep_clone();
+		Self {
+			root,
+			size: self.size,
+			base: self.base.clone(),
+			case_insensitive: self.case_insensitive,
+			relative: self.relative,
+		}
+	}
+}
+
+impl<T: PartialEq> PartialEq for PathStore<T> {
+	/// Two stores are equal if they have the same paths with the same data
+	///
+	/// A relative store is never equal to an absolute one, since `f` and `/f` are different paths.
+	/// Nothing else about the stores matters, such as the base or the order paths were added in
+	fn eq(&self, other: &Self) -> bool {
+		if self.relative != other.relative {
+			return false;
+		}
+		let mut stack = vec![(self.root.clone(), other.root.clone())];
+		while let Some((a, b)) = stack.pop() {
+			if Arc::ptr_eq(&a, &b) {
+				continue;
+			}
+			let a = a.read().expect("Failed to lock tree node when comparing trees");
+			let b = b.read().expect("Failed to lock tree node when comparing trees");
+			if a.data != b.data || a.items.len() != b.items.len() {
+				return false;
+			}
+			// Children are sorted, so the same names line up
+			for ((a_name, a_child), (b_name, b_child)) in a.items.iter().zip(b.items.iter()) {
+				if a_name != b_name {
+					return false;
+				}
+				stack.push((a_child.clone(), b_child.clone()));
+			}
+		}
+		true
+	}
+}
+
+impl<T: Eq> Eq for PathStore<T> {}
+
+impl<T> IntoIterator for PathStore<T> {
+	type Item = (PathBuf, T);
+	type IntoIter = IntoIter<T>;
+
+	/// Tear down the store, yielding every path with data along with the data
+	fn into_iter(self) -> IntoIter<T> {
+		IntoIter::new(self.root_path(), self.root.clone())
+	}
+}
+
+impl<T> FromIterator<(PathBuf, T)> for PathStore<T> {
+	/// Build a store by adding each path with its data, later duplicates overwrite earlier ones
+	///
+	/// Only absolute paths can be stored, any others are skipped
+	fn from_iter<I: IntoIterator<Item = (PathBuf, T)>>(iter: I) -> Self {
+		let mut store = PathStore::new(None);
+		store.extend(iter);
+		store
+	}
+}
+
+impl<T> Extend<(PathBuf, T)> for PathStore<T> {
+	/// Add each path with its data, same as calling `add_path` for each
+	///
+	/// Paths that can not be added, such as relative paths without a base, are skipped
+	fn extend<I: IntoIterator<Item = (PathBuf, T)>>(&mut self, iter: I) {
+		for (path, data) in iter {
+			let _ = self.add_path(path, Some(data));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{node_names, prefix_name, AddOutcome, Entry, PathStore, VisitControl};
+	use crate::errors::{InvalidComponentReason, StorageError};
+	use std::ffi::OsString;
+	use std::ffi::OsStr;
+	use std::path::{Path, PathBuf, Prefix};
+	use std::sync::{Arc, Weak};
+	use std::thread;
+
+	/// Check the size counter against the nodes actually in the tree
+	fn assert_size_consistent<T>(store: &PathStore<T>) {
+		assert_eq!(store.size(), store.walk_all().len() - 1, "size doesn't match the number of non-root nodes");
+	}
+
+	#[test]
+	fn root_store_eq() {
+		let mut a = PathStore::new(None);
+		assert_eq!(a.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(a.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(a.add_path("/g", Some(2)), Ok(true));
+
+		let mut b = PathStore::new(None);
+		assert_eq!(b.add_path("/g", Some(2)), Ok(true));
+		assert_eq!(b.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(b.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert!(a == b);
+		assert!(a == a.clone());
+
+		b.set_data("/f/FDrive/hello", Some(3)).unwrap();
+		assert!(a != b);
+		b.set_data("/f/FDrive/hello", None).unwrap();
+		assert!(a == b);
+
+		// Only paths and data count, not how the store got there
+		assert_eq!(b.add_path("/h/i", None), Ok(true));
+		assert!(a != b);
+		assert_eq!(b.remove_subtree("/h"), Ok(2));
+		assert!(a == b);
+		let mut c = PathStore::with_base("/f").unwrap();
+		assert_eq!(c.add_path("FDrive/files", Some(1)), Ok(true));
+		assert_eq!(c.add_path("FDrive/hello", None), Ok(true));
+		assert_eq!(c.add_path("/g", Some(2)), Ok(true));
+		assert!(a == c);
+		assert!(PathStore::new(Some(1)) != PathStore::new(None));
+
+		let mut relative = PathStore::new_relative(None);
+		assert_eq!(relative.add_path("g", Some(2)), Ok(true));
+		let mut absolute = PathStore::new(None);
+		assert_eq!(absolute.add_path("/g", Some(2)), Ok(true));
+		assert!(relative != absolute);
+		assert!(relative == relative.clone());
+		assert!(PathStore::<()>::new_relative(None) != PathStore::new(None));
+	}
+
+	#[test]
+	fn root_store_add_paths() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_paths(vec!["/f/FDrive/files", "relative", "/f/FDrive", "/g", "/.."]), vec![
+			Ok(true),
+			Err(StorageError::PathNotAbsolute { path: PathBuf::from("relative") }),
+			Ok(false),
+			Ok(true),
+			Err(StorageError::PathEscapesRoot { path: PathBuf::from("/..") }),
+		]);
+		assert_eq!(store.size(), 4);
+
+		assert_eq!(store.add_paths_with_data(vec![("/g", Some(1)), ("/h", None), ("h", Some(2))]), vec![
+			Ok(false),
+			Ok(true),
+			Err(StorageError::PathNotAbsolute { path: PathBuf::from("h") }),
+		]);
+		assert_eq!(store.walk_with_data(), vec![(PathBuf::from("/g"), 1)]);
+		assert_eq!(store.size(), 5);
+		assert_size_consistent(&store);
+	}
+
+	#[test]
+	fn root_store_push() {
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.size, 0);
+
+		assert_eq!(store.add_path("/f", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f", None), Ok(false));
+		assert!(store.add_path("h", None).is_err());
+		assert_eq!(store.size, 2);
+		assert_size_consistent(&store);
+	}
+
+	#[test]
+	fn root_store_push_double() {
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.size, 0);
+
+		assert_eq!(store.add_path("/f", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f", None), Ok(false));
+		assert!(store.add_path("h", None).is_err());
+		assert_eq!(store.size, 5);
+
+		let walk = store.walk();
+		assert_eq!(walk, vec![
+			PathBuf::from("/f/FDrive/files"),
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/g"),
+		]);
+	}
+
+	#[test]
+	fn root_store_remove() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", Some(3)), Ok(true));
+		assert_eq!(store.size, 5);
+
+		assert_eq!(store.remove_path("/f/FDrive/files"), Ok(Some(2)));
+		assert_eq!(store.size, 4);
+		assert_eq!(store.remove_path("/f/FDrive/files"), Ok(None));
+		assert_eq!(store.remove_path("/f/missing/path"), Ok(None));
+		assert!(store.remove_path("f").is_err());
+
+		// Only clears data as /f still has children
+		assert_eq!(store.remove_path("/f"), Ok(Some(1)));
+		assert_eq!(store.size, 4);
+
+		// Prunes the now empty /f/FDrive and /f as well
+		assert_eq!(store.remove_path("/f/FDrive/hello"), Ok(Some(3)));
+		assert_eq!(store.size, 1);
+
+		let walk = store.walk();
+		assert_eq!(walk, vec![PathBuf::from("/g")]);
+	}
+
+	#[test]
+	fn root_store_remove_keeps_data_ancestors() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/a", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/c", Some(2)), Ok(true));
+		assert_eq!(store.size, 3);
+
+		assert_eq!(store.remove_path("/a/b/c"), Ok(Some(2)));
+		assert_eq!(store.size, 1);
+		assert_eq!(store.walk(), vec![PathBuf::from("/"), PathBuf::from("/a")]);
+
+		assert_eq!(store.remove_path("/"), Ok(Some(0)));
+		assert_eq!(store.size, 1);
+	}
+
+	#[test]
+	fn root_store_contains() {
+		let mut store = PathStore::new(None::<()>);
+		assert!(store.contains("/"));
+		assert!(!store.contains("/f"));
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert!(store.contains("/"));
+		assert!(store.contains("/f"));
+		assert!(store.contains("/f/FDrive"));
+		assert!(store.contains("/f/FDrive/files"));
+		assert!(!store.contains("/f/FDrive/hello"));
+		assert!(!store.contains("/f/FD"));
+		assert!(!store.contains("/f/FDrive/files/deeper"));
+		assert!(!store.contains("f/FDrive"));
+		assert_eq!(store.size, 3);
+
+		assert!(store.contains_path("/"));
+		assert!(store.contains_path("/f/FDrive"));
+		assert!(!store.contains_path("/f/FD"));
+	}
+
+	#[test]
+	fn root_store_remove_subtree() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/f", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.size, 5);
+
+		let removed_node = Arc::downgrade(&store.get_node("/f/FDrive".as_ref()).unwrap());
+		assert_eq!(store.remove_subtree("/f/FDrive"), Ok(3));
+		assert_eq!(store.size, 2);
+		assert_eq!(Weak::strong_count(&removed_node), 0);
+		assert!(!store.contains("/f/FDrive/files"));
+		assert!(!store.contains("/f/FDrive/hello"));
+
+		assert_eq!(store.remove_subtree("/f/FDrive"), Ok(0));
+		assert_eq!(store.remove_subtree("/missing/path"), Ok(0));
+		assert!(store.remove_subtree("f").is_err());
+		assert_eq!(store.size, 2);
+
+		// Resolves to the root, so everything goes
+		assert_eq!(store.remove_subtree("/f/.."), Ok(2));
+		assert_eq!(store.size, 0);
+	}
+
+	#[test]
+	fn root_store_remove_subtree_root() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2)), Ok(true));
+		assert_eq!(store.remove_subtree("/"), Ok(4));
+		assert_eq!(store.size, 0);
+		assert!(store.contains("/"));
+		assert_eq!(store.remove_path("/"), Ok(Some(0)));
+	}
+
+	#[test]
+	fn root_store_get_cloned() {
+		let mut store = PathStore::new(Some("root".to_owned()));
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some("files".to_owned())), Ok(true));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some("files".to_owned()));
+		assert_eq!(store.get_cloned("/"), Some("root".to_owned()));
+		assert_eq!(store.get_cloned("/f/FDrive"), None);
+		assert_eq!(store.get_cloned("/f/FDrive/hello"), None);
+		assert_eq!(store.get_cloned("f/FDrive/files"), None);
+	}
+
+	#[test]
+	fn root_store_contains_entry() {
+		let mut store = PathStore::new(None);
+		assert!(!store.contains_entry("/"));
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert!(store.contains_entry("/f/FDrive/files"));
+		assert!(store.contains("/f/FDrive"));
+		assert!(!store.contains_entry("/f/FDrive"));
+		assert!(!store.contains_entry("/f/FDrive/hello"));
+		assert!(!store.contains_entry("f/FDrive/files"));
+
+		let store = PathStore::new(Some(0));
+		assert!(store.contains_entry("/"));
+	}
+
+	#[test]
+	fn root_store_walk_with_data() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		let walk = store.walk_with_data();
+		assert_eq!(walk, vec![
+			(PathBuf::from("/f"), 1),
+			(PathBuf::from("/f/FDrive/files"), 2),
+		]);
+
+		let store = PathStore::new(Some(0));
+		assert_eq!(store.walk_with_data(), vec![(PathBuf::from("/"), 0)]);
+	}
+
+	#[test]
+	fn root_store_get_with() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(vec![1, 2, 3])), Ok(true));
+		assert_eq!(store.get_with("/f/FDrive/files", |d| d.len()), Some(3));
+		assert_eq!(store.get_with("/f/FDrive/files/more", |d| d.len()), None);
+
+		// Parents created implicitly have no data
+		assert_eq!(store.get_with("/f", |d| d.len()), None);
+		assert_eq!(store.get_with("/f/FDrive", |d| d.len()), None);
+		assert_eq!(store.get_cloned("/f/FDrive"), None);
+		assert_eq!(store.get_with("/", |d| d.len()), None);
+	}
+
+	#[test]
+	fn root_store_walk_data_parents() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		let walk = store.walk();
+		assert_eq!(walk, vec![
+			PathBuf::from("/f"),
+			PathBuf::from("/f/FDrive/files"),
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/g"),
+		]);
+	}
+
+	#[test]
+	fn root_store_with_data_mut() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.with_data_mut("/f/FDrive/files", |d| {
+			*d.as_mut().unwrap() += 1;
+		}), Ok(()));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(2));
+
+		assert_eq!(store.with_data_mut("/f", |d| d.replace(5)), Ok(None));
+		assert_eq!(store.get_cloned("/f"), Some(5));
+		assert_eq!(store.with_data_mut("/f", |d| d.take()), Ok(Some(5)));
+		assert_eq!(store.get_cloned("/f"), None);
+
+		assert_eq!(store.with_data_mut("/f/FDrive/hello", |_| ()), Err(StorageError::PathNotFound { path: PathBuf::from("/f/FDrive/hello") }));
+		assert_eq!(store.with_data_mut("f/FDrive", |_| ()), Err(StorageError::PathNotAbsolute { path: PathBuf::from("f/FDrive") }));
+		assert!(!store.contains("/f/FDrive/hello"));
+		assert_eq!(store.size, 3);
+	}
+
+	#[test]
+	fn root_store_walk_sorted() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/b/z", None), Ok(true));
+		assert_eq!(store.add_path("/a", None), Ok(true));
+		assert_eq!(store.add_path("/b/B", None), Ok(true));
+		assert_eq!(store.add_path("/b/a", None), Ok(true));
+
+		assert_eq!(store.walk(), vec![
+			PathBuf::from("/a"),
+			PathBuf::from("/b/B"),
+			PathBuf::from("/b/a"),
+			PathBuf::from("/b/z"),
+		]);
+	}
+
+	#[test]
+	fn root_store_entry() {
+		let mut store = PathStore::new(None);
+
+		*store.entry("/f/FDrive/files").unwrap().or_insert(0) += 1;
+		assert_eq!(store.size, 3);
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(1));
+
+		*store.entry("/f/FDrive/files").unwrap().or_insert(0) += 1;
+		assert_eq!(store.size, 3);
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(2));
+
+		// Existing data is not overwritten
+		assert_eq!(*store.entry("/f/FDrive/files").unwrap().or_insert(10), 2);
+		assert_eq!(*store.entry("/f/FDrive/files").unwrap().or_insert_with(|| panic!("Already set")), 2);
+
+		store.entry("/f/FDrive/hello").unwrap().and_modify(|d| *d += 1).or_insert(5);
+		store.entry("/f/FDrive/files").unwrap().and_modify(|d| *d += 1).or_insert(5);
+		assert_eq!(store.get_cloned("/f/FDrive/hello"), Some(5));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(3));
+		assert_eq!(store.size, 4);
+
+		// Parents are created even without inserting
+		store.entry("/g/h").unwrap();
+		assert!(store.contains("/g/h"));
+		assert_eq!(store.get_cloned("/g/h"), None);
+		assert_eq!(store.size, 6);
+
+		assert!(store.entry("f").is_err());
+	}
+
+	#[test]
+	fn root_store_entry_variants() {
+		let mut store = PathStore::new(None);
+
+		match store.entry("/a/b").unwrap() {
+			Entry::Vacant(entry) => {
+				let mut entry = entry.insert(1);
+				*entry.get_mut() += 1;
+				assert_eq!(entry.insert(5), 2);
+			}
+			Entry::Occupied(_) => panic!("New path has no data"),
+		}
+		assert_eq!(store.get_cloned("/a/b"), Some(5));
+
+		match store.entry("/a/./b").unwrap() {
+			Entry::Occupied(entry) => assert_eq!(entry.take(), 5),
+			Entry::Vacant(_) => panic!("Path has data"),
+		}
+		assert!(store.contains("/a/b"));
+		assert!(matches!(store.entry("/a/b").unwrap(), Entry::Vacant(_)));
+
+		// Filling a vacant entry makes it occupied
+		let mut entry = store.entry("/a").unwrap();
+		*entry.or_insert(1) += 1;
+		entry.and_modify(|d| *d *= 10);
+		assert!(matches!(entry, Entry::Occupied(_)));
+		drop(entry);
+		assert_eq!(store.get_cloned("/a"), Some(20));
+		assert_eq!(store.size(), 2);
+	}
+
+	#[test]
+	fn root_store_get_or_insert_with() {
+		let mut store = PathStore::new(None);
+
+		let mut entry = store.get_or_insert_with("/f/FDrive/files", Vec::new).unwrap();
+		entry.get_mut().push(1);
+		drop(entry);
+		assert_eq!(store.size, 3);
+
+		let mut entry = store.get_or_insert_with("/f/FDrive/files", || panic!("Already set")).unwrap();
+		entry.get_mut().push(2);
+		drop(entry);
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(vec![1, 2]));
+		assert_eq!(store.size, 3);
+
+		// Nodes that exist without data still get it
+		assert_eq!(*store.get_or_insert_with("/f", || vec![3]).unwrap().get_mut(), vec![3]);
+		assert!(matches!(store.entry("/g").unwrap(), Entry::Vacant(_)));
+		assert_eq!(store.size, 4);
+
+		assert!(store.get_or_insert_with("f", Vec::new).is_err());
+	}
+
+	#[test]
+	fn root_store_iter() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		let iter: Vec<_> = store.iter().collect();
+		assert_eq!(iter, store.walk());
+		assert_eq!(store.iter().take(2).collect::<Vec<_>>(), vec![
+			PathBuf::from("/f"),
+			PathBuf::from("/f/FDrive/files"),
+		]);
+		assert_eq!(store.iter().find(|p| p.ends_with("hello")), Some(PathBuf::from("/f/FDrive/hello")));
+
+		let store = PathStore::new(None::<()>);
+		assert_eq!(store.iter().collect::<Vec<_>>(), vec![PathBuf::from("/")]);
+	}
+
+	#[test]
+	fn root_store_take_data() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+
+		assert_eq!(store.take_data("/f"), Some(1));
+		assert_eq!(store.take_data("/f"), None);
+		assert!(store.contains("/f/FDrive/files"));
+		assert_eq!(store.size, 3);
+
+		assert_eq!(store.take_data("/"), Some(0));
+		assert_eq!(store.take_data("/f/FDrive/hello"), None);
+		assert_eq!(store.walk_with_data(), vec![(PathBuf::from("/f/FDrive/files"), 2)]);
+	}
+
+	#[test]
+	fn root_store_children() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		assert_eq!(store.children("/"), Some(vec![OsString::from("f"), OsString::from("g")]));
+		assert_eq!(store.children("/f/FDrive"), Some(vec![OsString::from("files"), OsString::from("hello")]));
+		assert_eq!(store.children("/g"), Some(vec![]));
+		assert_eq!(store.children("/h"), None);
+		assert_eq!(store.children("f"), None);
+
+		assert_eq!(store.children_expandable("/"), Some(vec![(OsString::from("f"), true), (OsString::from("g"), false)]));
+		assert_eq!(store.children_expandable("/g"), Some(vec![]));
+		assert_eq!(store.children_expandable("/h"), None);
+	}
+
+	#[test]
+	fn root_store_complete() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f/Fonts", None), Ok(true));
+
+		assert_eq!(store.complete("/f/FD"), vec![PathBuf::from("/f/FDrive")]);
+		assert_eq!(store.complete("/f/F"), vec![PathBuf::from("/f/FDrive"), PathBuf::from("/f/Fonts")]);
+		assert_eq!(store.complete("/f/FDrive"), vec![PathBuf::from("/f/FDrive")]);
+		assert_eq!(store.complete("/f/FDrive/"), vec![PathBuf::from("/f/FDrive/files"), PathBuf::from("/f/FDrive/hello")]);
+		assert_eq!(store.complete("/"), vec![PathBuf::from("/f"), PathBuf::from("/g")]);
+		assert_eq!(store.complete("/f/fd"), Vec::<PathBuf>::new());
+		assert_eq!(store.complete("/h/x"), Vec::<PathBuf>::new());
+		assert_eq!(store.complete("f/FD"), Vec::<PathBuf>::new());
+
+		let mut store = PathStore::new_case_insensitive(None::<()>);
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.complete("/F/fd"), vec![PathBuf::from("/f/FDrive")]);
+	}
+
+	#[test]
+	fn root_store_insert() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.insert("/f/FDrive/files", 1), Ok(None));
+		assert_eq!(store.insert("/f/FDrive/files", 2), Ok(Some(1)));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(2));
+
+		// Parent exists but has no data yet
+		assert_eq!(store.insert("/f", 3), Ok(None));
+		assert_eq!(store.insert("/f", 4), Ok(Some(3)));
+		assert_eq!(store.insert("/", 5), Ok(None));
+		assert_eq!(store.size, 3);
+
+		assert_eq!(store.insert("f", 6), Err(StorageError::PathNotAbsolute { path: PathBuf::from("f") }));
+	}
+
+	#[test]
+	fn root_store_subtree() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello/world", None), Ok(true));
+
+		let mut sub = store.subtree("/f/FDrive").unwrap();
+		assert_eq!(sub.size(), 3);
+		assert_eq!(sub.get_cloned("/"), Some(1));
+		assert_eq!(sub.walk_with_data(), vec![(PathBuf::from("/"), 1), (PathBuf::from("/files"), 2)]);
+
+		// The original is independent of the copy
+		assert_eq!(sub.insert("/files", 3), Ok(Some(2)));
+		assert_eq!(sub.add_path("/new", None), Ok(true));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(2));
+		assert!(!store.contains("/f/FDrive/new"));
+		assert_eq!(store.size(), 6);
+
+		assert!(store.subtree("/h").is_none());
+	}
+
+	#[test]
+	fn root_store_push_keeps_data() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f", None), Ok(false));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(false));
+		assert_eq!(store.get_cloned("/f"), Some(1));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(2));
+
+		assert_eq!(store.add_path("/f", Some(3)), Ok(false));
+		assert_eq!(store.get_cloned("/f"), Some(3));
+	}
+
+	#[test]
+	fn root_store_push_detailed() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path_detailed("/f", None), Ok(AddOutcome::Inserted { new_nodes: 1 }));
+		assert_eq!(store.add_path_detailed("/f/FDrive/files", Some(1)), Ok(AddOutcome::Inserted { new_nodes: 2 }));
+		assert_eq!(store.add_path_detailed("/f/FDrive/files", Some(2)), Ok(AddOutcome::Updated));
+		assert_eq!(store.add_path_detailed("/f/FDrive", None), Ok(AddOutcome::Unchanged));
+		assert_eq!(store.add_path_detailed("/", Some(3)), Ok(AddOutcome::Updated));
+		assert!(store.add_path_detailed("f", None).is_err());
+		assert_eq!(store.size, 3);
+
+		assert!(bool::from(AddOutcome::Inserted { new_nodes: 1 }));
+		assert!(!bool::from(AddOutcome::Updated));
+		assert!(!bool::from(AddOutcome::Unchanged));
+	}
+
+	#[test]
+	fn root_store_with_base() {
+		let mut store = PathStore::with_base("/home/user").unwrap();
+
+		assert_eq!(store.add_path("docs/a.txt", Some(1)), Ok(true));
+		assert_eq!(store.size, 4);
+		assert!(store.contains("/home/user/docs/a.txt"));
+		assert_eq!(store.get_cloned("docs/a.txt"), Some(1));
+		assert_eq!(store.get_cloned("./docs/../docs/a.txt"), Some(1));
+
+		assert_eq!(store.add_path("/etc/hosts", None), Ok(true));
+		assert_eq!(store.add_path("docs/../../other", None), Err(StorageError::PathEscapesBase { path: PathBuf::from("docs/../../other") }));
+		assert_eq!(store.add_path("..", None), Err(StorageError::PathEscapesBase { path: PathBuf::from("..") }));
+		assert!(!store.contains("../user/docs"));
+		assert_eq!(store.size, 6);
+
+		assert_eq!(store.remove_path("docs/a.txt"), Ok(Some(1)));
+		assert!(!store.contains("/home/user/docs"));
+
+		assert!(PathStore::<()>::with_base("home/user").is_err());
+	}
+
+	#[test]
+	fn root_store_iter_drop_early() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", Some(2)), Ok(true));
+
+		let mut iter = store.iter();
+		assert_eq!(iter.next(), Some(PathBuf::from("/f/FDrive/files")));
+		drop(iter);
+
+		// Nothing is left locked
+		assert_eq!(store.insert("/f/FDrive/files", 3), Ok(Some(1)));
+		assert_eq!(store.remove_subtree("/f"), Ok(4));
+	}
+
+	#[test]
+	fn root_store_into_iter() {
+		let mut store = PathStore::new(Some("root".to_owned()));
+
+		assert_eq!(store.add_path("/f", Some("f".to_owned())), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some("files".to_owned())), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		let mut out = Vec::new();
+		for (path, data) in store {
+			out.push((path, data));
+		}
+		assert_eq!(out, vec![
+			(PathBuf::from("/"), "root".to_owned()),
+			(PathBuf::from("/f"), "f".to_owned()),
+			(PathBuf::from("/f/FDrive/files"), "files".to_owned()),
+		]);
+	}
+
+	#[test]
+	fn root_store_iter_entries() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/x", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+
+		assert_eq!(store.iter_entries().collect::<Vec<_>>(), vec![
+			(PathBuf::from("/"), 0),
+			(PathBuf::from("/f"), 1),
+			(PathBuf::from("/f/FDrive/files"), 2),
+		]);
+		assert_eq!(store.iter_entries().nth(1), Some((PathBuf::from("/f"), 1)));
+	}
+
+	#[test]
+	fn root_store_from_iter() {
+		let mut store: PathStore<u64> = vec![
+			(PathBuf::from("/f/FDrive/files"), 1),
+			(PathBuf::from("/g"), 2),
+			(PathBuf::from("relative"), 3),
+			(PathBuf::from("/g"), 4),
+		]
+		.into_iter()
+		.collect();
+		assert_eq!(store.size, 4);
+		assert_eq!(store.walk_with_data(), vec![
+			(PathBuf::from("/f/FDrive/files"), 1),
+			(PathBuf::from("/g"), 4),
+		]);
+
+		store.extend(vec![(PathBuf::from("/f"), 5), (PathBuf::from("/h"), 6)]);
+		assert_eq!(store.size, 5);
+		assert_eq!(store.get_cloned("/f"), Some(5));
+		assert_eq!(store.get_cloned("/h"), Some(6));
+	}
+
+	#[test]
+	fn root_store_walk_round_trip() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut copy = PathStore::new(None::<()>);
+		for path in store.walk() {
+			assert!(path.is_absolute());
+			assert_eq!(store.add_path(&path, None), Ok(false));
+			assert_eq!(copy.add_path(&path, None), Ok(true));
+		}
+		assert_eq!(copy.walk(), store.walk());
+		assert_eq!(copy.size, store.size);
+	}
+
+	#[test]
+	fn root_store_clone() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut copy = store.clone();
+		assert_eq!(copy.size, 4);
+		assert_eq!(copy.walk_with_data(), store.walk_with_data());
+		assert!(!Arc::ptr_eq(&copy.root, &store.root));
+
+		assert_eq!(copy.add_path("/f/FDrive/hello", Some(2)), Ok(true));
+		assert_eq!(copy.insert("/f/FDrive/files", 3), Ok(Some(1)));
+		assert_eq!(copy.insert("/", 4), Ok(Some(0)));
+		assert_eq!(copy.size, 5);
+
+		assert_eq!(store.size, 4);
+		assert!(!store.contains("/f/FDrive/hello"));
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(1));
+		assert_eq!(store.get_cloned("/"), Some(0));
+	}
+
+	#[test]
+	fn root_store_insertion_order() {
+		let paths = ["/f/FDrive/hello", "/g", "/f/FDrive/files", "/f/a", "/G"];
+
+		let mut first = PathStore::new(None::<()>);
+		for path in paths.iter() {
+			assert_eq!(first.add_path(path, None), Ok(true));
+		}
+		let mut second = PathStore::new(None::<()>);
+		for path in paths.iter().rev() {
+			assert_eq!(second.add_path(path, None), Ok(true));
+		}
+
+		assert_eq!(first.walk(), second.walk());
+		assert_eq!(first.iter().collect::<Vec<_>>(), second.iter().collect::<Vec<_>>());
+		assert_eq!(first.walk(), vec![
+			PathBuf::from("/G"),
+			PathBuf::from("/f/FDrive/files"),
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/f/a"),
+			PathBuf::from("/g"),
+		]);
+	}
+
+	#[test]
+	fn root_store_merge() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2)), Ok(true));
+
+		let mut other = PathStore::new(Some(0));
+		assert_eq!(other.add_path("/f/FDrive/hello", Some(3)), Ok(true));
+		assert_eq!(other.add_path("/g", Some(4)), Ok(true));
+		assert_eq!(other.add_path("/h/i", None), Ok(true));
+
+		store.merge(other);
+		assert_eq!(store.size, 7);
+		assert_eq!(store.walk_with_data(), vec![
+			(PathBuf::from("/"), 0),
+			(PathBuf::from("/f/FDrive/files"), 1),
+			(PathBuf::from("/f/FDrive/hello"), 3),
+			(PathBuf::from("/g"), 4),
+		]);
+		assert!(store.contains("/h/i"));
+	}
+
+	#[test]
+	fn root_store_merge_with() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2)), Ok(true));
+
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/f", Some(10)), Ok(true));
+		assert_eq!(other.add_path("/f/FDrive", Some(20)), Ok(true));
+
+		store.merge_with(other, |existing, incoming| existing + incoming);
+		assert_eq!(store.size, 3);
+		assert_eq!(store.walk_with_data(), vec![
+			(PathBuf::from("/f"), 11),
+			(PathBuf::from("/f/FDrive"), 20),
+			(PathBuf::from("/g"), 2),
+		]);
+
+		// The merged in nodes are part of the tree like any other
+		assert_eq!(store.remove_subtree("/f"), Ok(2));
+		assert_eq!(store.size, 1);
+	}
+
+	#[test]
+	fn root_store_walk_all() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+
+		let walk = store.walk_all();
+		assert_eq!(walk, vec![
+			PathBuf::from("/"),
+			PathBuf::from("/f"),
+			PathBuf::from("/f/FDrive"),
+			PathBuf::from("/f/FDrive/files"),
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/g"),
+		]);
+		assert_eq!(walk.len(), store.size + 1);
+		for (i, path) in walk.iter().enumerate() {
+			if let Some(parent) = path.parent() {
+				assert!(walk[..i].iter().any(|p| p == parent));
+			}
+		}
+	}
+
+	#[test]
+	fn root_store_set_data() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.set_data("/f/FDrive", Some(2)), Ok(true));
+		assert_eq!(store.set_data("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.set_data("/f/FDrive/hello", Some(3)), Ok(false));
+		assert_eq!(store.set_data("f/FDrive", Some(3)), Err(StorageError::PathNotAbsolute { path: PathBuf::from("f/FDrive") }));
+		assert!(!store.contains("/f/FDrive/hello"));
+		assert_eq!(store.walk_with_data(), vec![(PathBuf::from("/f/FDrive"), 2)]);
+
+		assert_eq!(store.replace_data("/f/FDrive", 4), Some(2));
+		assert_eq!(store.replace_data("/f/FDrive/files", 5), None);
+		assert_eq!(store.get_cloned("/f/FDrive/files"), Some(5));
+		assert_eq!(store.replace_data("/f/FDrive/hello", 6), None);
+		assert!(!store.contains("/f/FDrive/hello"));
+		assert_eq!(store.size, 3);
+	}
+
+	#[test]
+	fn root_store_walk_data_paths() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		assert_eq!(store.walk_data_paths(), vec![
+			PathBuf::from("/f"),
+			PathBuf::from("/f/FDrive/hello"),
+		]);
+		assert_eq!(store.iter_data_paths().next(), Some(PathBuf::from("/f")));
+	}
+
+	#[test]
+	fn root_store_iter_bfs() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/a", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		assert_eq!(store.iter_bfs().collect::<Vec<_>>(), vec![
+			(0, PathBuf::from("/")),
+			(1, PathBuf::from("/f")),
+			(1, PathBuf::from("/g")),
+			(2, PathBuf::from("/f/FDrive")),
+			(2, PathBuf::from("/f/a")),
+			(3, PathBuf::from("/f/FDrive/files")),
+		]);
+	}
+
+	#[test]
+	fn root_store_walk_bfs() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/f/FDrive", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let walk = store.walk_bfs();
+		assert_eq!(walk, vec![
+			PathBuf::from("/"),
+			PathBuf::from("/f"),
+			PathBuf::from("/g"),
+			PathBuf::from("/f/FDrive"),
+		]);
+		assert_eq!(walk.len(), store.walk_all().len());
+	}
+
+	#[test]
+	fn root_store_insert_matches_push() {
+		let mut pushed = PathStore::new(None);
+		let mut inserted = PathStore::new(None);
+
+		for (path, data) in [("/f/FDrive/files", 1), ("/f", 2), ("/f/FDrive/files", 3)].iter() {
+			let _ = pushed.add_path(path, Some(*data));
+			let _ = inserted.insert(path, *data);
+		}
+		assert_eq!(pushed.size, inserted.size);
+		assert_eq!(pushed.walk_with_data(), inserted.walk_with_data());
+
+		// Overwrites can be detected and the old data dealt with
+		assert_eq!(inserted.insert("/f/FDrive/files", 4), Ok(Some(3)));
+		assert_eq!(pushed.add_path("/f/FDrive/files", Some(4)), Ok(false));
+	}
+
+	#[test]
+	fn root_store_send_sync() {
+		fn assert_send_sync<T: Send + Sync>() {}
+		assert_send_sync::<PathStore<String>>();
+
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2)), Ok(true));
+
+		// Shared between threads
+		thread::scope(|s| {
+			let first = s.spawn(|| store.get_cloned("/f/FDrive/files"));
+			let second = s.spawn(|| store.walk());
+			assert_eq!(first.join().unwrap(), Some(1));
+			assert_eq!(second.join().unwrap().len(), 2);
+		});
+
+		// Moved to another thread
+		let walk = thread::spawn(move || store.walk_with_data()).join().unwrap();
+		assert_eq!(walk, vec![(PathBuf::from("/f/FDrive/files"), 1), (PathBuf::from("/g"), 2)]);
+	}
+
+	#[test]
+	fn root_store_iter_postorder() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/a/b/c", None), Ok(true));
+		assert_eq!(store.add_path("/a/d", None), Ok(true));
+
+		let walk: Vec<_> = store.iter_postorder().collect();
+		assert_eq!(walk, vec![
+			PathBuf::from("/a/b/c"),
+			PathBuf::from("/a/b"),
+			PathBuf::from("/a/d"),
+			PathBuf::from("/a"),
+			PathBuf::from("/"),
+		]);
+		for (i, path) in walk.iter().enumerate() {
+			if let Some(parent) = path.parent() {
+				assert!(walk[i + 1..].iter().any(|p| p == parent));
+			}
+		}
+	}
+
+	#[test]
+	fn root_store_walk_depth() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f/a", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		// Depth 0 is the root, 1 is /f and /g, 2 is /f/FDrive and /f/a, 3 is the rest
+		assert_eq!(store.walk_depth(0), vec![PathBuf::from("/")]);
+		assert_eq!(store.walk_depth(1), vec![PathBuf::from("/f"), PathBuf::from("/g")]);
+		assert_eq!(store.walk_depth(2), vec![
+			PathBuf::from("/f/FDrive"),
+			PathBuf::from("/f/a"),
+			PathBuf::from("/g"),
+		]);
+		assert_eq!(store.walk_depth(3), store.walk());
+		assert_eq!(store.walk_depth(4), store.walk());
+		assert_eq!(store.walk_depth(usize::MAX), store.walk());
+		assert_eq!(store.iter_depth(1).collect::<Vec<_>>(), store.walk_depth(1));
+		assert_eq!(store.iter_depth(2).next(), Some(PathBuf::from("/f/FDrive")));
+	}
+
+	#[test]
+	fn root_store_prune() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/d", None), Ok(true));
+		assert_eq!(store.add_path("/x/y", Some(2)), Ok(true));
+		assert_eq!(store.take_data("/x/y"), Some(2));
+		assert_eq!(store.size, 6);
+
+		store.prune();
+		assert_eq!(store.size, 3);
+		assert_eq!(store.walk_all(), vec![
+			PathBuf::from("/"),
+			PathBuf::from("/a"),
+			PathBuf::from("/a/b"),
+			PathBuf::from("/a/b/c"),
+		]);
+
+		assert_eq!(store.take_data("/a/b/c"), Some(1));
+		store.prune();
+		assert_eq!(store.size, 0);
+		assert_eq!(store.walk_all(), vec![PathBuf::from("/")]);
+	}
+
+	#[test]
+	fn root_store_visit() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/node_modules/a/index.js", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/src/lib.rs", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/src/main.rs", Some(3)), Ok(true));
+		assert_eq!(store.add_path("/target/debug", None), Ok(true));
+
+		let mut visited = Vec::new();
+		store.visit(|path, data| {
+			visited.push((path.to_path_buf(), data.copied()));
+			if path.ends_with("node_modules") {
+				VisitControl::SkipSubtree
+			} else {
+				VisitControl::Continue
+			}
+		});
+		assert_eq!(visited, vec![
+			(PathBuf::from("/"), None),
+			(PathBuf::from("/node_modules"), None),
+			(PathBuf::from("/src"), None),
+			(PathBuf::from("/src/lib.rs"), Some(2)),
+			(PathBuf::from("/src/main.rs"), Some(3)),
+			(PathBuf::from("/target"), None),
+			(PathBuf::from("/target/debug"), None),
+		]);
+
+		let mut visited = Vec::new();
+		store.visit(|path, _| {
+			visited.push(path.to_path_buf());
+			if path.ends_with("lib.rs") {
+				VisitControl::Stop
+			} else {
+				VisitControl::Continue
+			}
+		});
+		assert_eq!(visited.last(), Some(&PathBuf::from("/src/lib.rs")));
+		assert_eq!(visited.len(), 6);
+	}
+
+	#[test]
+	fn root_store_clear() {
+		let mut store = PathStore::new(Some(0));
+		assert!(store.is_empty());
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert!(!store.is_empty());
+
+		let old_root = Arc::downgrade(&store.root);
+		let old_node = Arc::downgrade(&store.get_node("/f/FDrive/files".as_ref()).unwrap());
+		store.clear();
+		assert!(store.is_empty());
+		assert_eq!(store.size, 0);
+		assert_eq!(Weak::strong_count(&old_root), 0);
+		assert_eq!(Weak::strong_count(&old_node), 0);
+		assert_eq!(store.get_cloned("/"), None);
+		assert_eq!(store.walk_all(), vec![PathBuf::from("/")]);
+
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert!(!store.is_empty());
+
+		assert_eq!(store.set_data("/", Some(0)), Ok(true));
+		let old_root = Arc::downgrade(&store.root);
+		store.clear_keep_root_data();
+		assert!(store.is_empty());
+		assert_eq!(Weak::strong_count(&old_root), 0);
+		assert_eq!(store.get_cloned("/"), Some(0));
+		assert_eq!(store.walk_all(), vec![PathBuf::from("/")]);
+	}
+
+	#[test]
+	fn root_store_deep_path() {
+		let depth = 100_000;
+		let mut path = PathBuf::from("/");
+		for _ in 0..depth {
+			path.push("a");
+		}
+
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path(&path, Some(1)), Ok(true));
+		assert_eq!(store.size, depth);
+		assert_eq!(store.walk(), vec![path.clone()]);
+		assert_eq!(store.walk_with_data(), vec![(path.clone(), 1)]);
+		assert_eq!(store.get_cloned(&path), Some(1));
+
+		let copy = store.subtree("/a/a").unwrap();
+		assert_eq!(copy.size, depth - 2);
+		drop(copy);
+		drop(store);
+	}
+
+	#[test]
+	fn root_store_walk_under() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/home/user/projects/a/src", None), Ok(true));
+		assert_eq!(store.add_path("/home/user/projects/b", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/home/user/projects/b/c", None), Ok(true));
+		assert_eq!(store.add_path("/home/other", None), Ok(true));
+
+		assert_eq!(store.walk_under("/home/user/projects"), Ok(vec![
+			PathBuf::from("/home/user/projects/a/src"),
+			PathBuf::from("/home/user/projects/b"),
+			PathBuf::from("/home/user/projects/b/c"),
+		]));
+		assert_eq!(store.walk_under("/home/user/./projects/b/"), Ok(vec![
+			PathBuf::from("/home/user/projects/b"),
+			PathBuf::from("/home/user/projects/b/c"),
+		]));
+		assert_eq!(store.walk_under("/home/other"), Ok(vec![PathBuf::from("/home/other")]));
+		assert_eq!(store.walk_under("/"), Ok(store.walk()));
+		assert_eq!(store.walk_under("/home/missing"), Err(StorageError::PathNotFound { path: PathBuf::from("/home/missing") }));
+		assert_eq!(store.walk_under("home"), Err(StorageError::PathNotAbsolute { path: PathBuf::from("home") }));
+	}
+
+	#[test]
+	fn root_store_walk_relative() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/c/d.txt", None), Ok(true));
+		assert_eq!(store.add_path("/a/b/e", None), Ok(true));
+		assert_eq!(store.add_path("/a/x", None), Ok(true));
+
+		let walk = store.walk_relative("/a/b").unwrap();
+		assert_eq!(walk, vec![PathBuf::new(), PathBuf::from("c/d.txt"), PathBuf::from("e")]);
+		assert_eq!(
+			walk.iter().map(|p| Path::new("/new").join(p)).collect::<Vec<_>>(),
+			vec![PathBuf::from("/new/"), PathBuf::from("/new/c/d.txt"), PathBuf::from("/new/e")]
+		);
+
+		assert_eq!(store.walk_relative("/a/b/c"), Ok(vec![PathBuf::from("d.txt")]));
+		assert_eq!(store.walk_relative("/a/missing"), Err(StorageError::PathNotFound { path: PathBuf::from("/a/missing") }));
+		assert_eq!(store.walk_relative("/").unwrap().len(), store.walk().len());
+	}
+
+	#[test]
+	fn root_store_paths_under() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/foo/bar/x", None), Ok(true));
+		assert_eq!(store.add_path("/foo/bar/y", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/foo/bar/y/z", None), Ok(true));
+		assert_eq!(store.add_path("/foo/baz", None), Ok(true));
+
+		assert_eq!(store.paths_under("/foo/bar"), vec![
+			PathBuf::from("/foo/bar/x"),
+			PathBuf::from("/foo/bar/y"),
+			PathBuf::from("/foo/bar/y/z"),
+		]);
+		// String prefix matching would wrongly pick up /foo/bar and /foo/baz here
+		assert_eq!(store.paths_under("/foo/ba"), Vec::<PathBuf>::new());
+		assert_eq!(store.paths_under("/foo/baz"), Vec::<PathBuf>::new());
+		assert_eq!(store.paths_under("/"), store.walk());
+		assert_eq!(store.paths_under("foo"), Vec::<PathBuf>::new());
+	}
+
+	#[test]
+	fn root_store_longest_prefix() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/c", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/a/b/c/d/x", None), Ok(true));
+
+		assert_eq!(store.longest_prefix("/a/b/c/d/e"), Some(PathBuf::from("/a/b/c")));
+		assert_eq!(store.longest_prefix("/a/b/c"), Some(PathBuf::from("/a/b/c")));
+		assert_eq!(store.longest_prefix("/a/b"), Some(PathBuf::from("/a")));
+		assert_eq!(store.longest_prefix("/a/bc"), Some(PathBuf::from("/a")));
+		assert_eq!(store.longest_prefix("/z"), None);
+
+		store.set_data("/", Some(0)).unwrap();
+		assert_eq!(store.longest_prefix("/z"), Some(PathBuf::from("/")));
+	}
+
+	#[test]
+	fn root_store_count_under() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/d", None), Ok(true));
+		assert_eq!(store.add_path("/a/e", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f", None), Ok(true));
+
+		assert_eq!(store.count_under("/a"), 4);
+		assert_eq!(store.count_under("/a/b"), 2);
+		assert_eq!(store.count_under("/a/b/c"), 0);
+		assert_eq!(store.count_under("/"), store.size());
+		assert_eq!(store.count_under("/missing"), 0);
+
+		assert_eq!(store.count_data_under("/a"), 2);
+		assert_eq!(store.count_data_under("/a/b"), 1);
+		assert_eq!(store.count_data_under("/"), 2);
+		assert_eq!(store.count_data_under("/missing"), 0);
+	}
+
+	/// Check the cached count of every node against counting the paths under it
+	fn assert_counts(store: &PathStore<i32>) {
+		let paths = store.walk_all();
+		for path in &paths {
+			let below = paths.iter().filter(|other| other.starts_with(path) && *other != path).count();
+			assert_eq!(store.count_under(path), below, "{:?}", path);
+		}
+		assert_eq!(store.count_under(store.root_path()), store.size());
+	}
+
+	#[test]
+	fn root_store_count_under_cached() {
+		let mut store = PathStore::new(Some(0));
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/d", None), Ok(true));
+		assert_eq!(store.add_path("/a/e/f/g", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/h", None), Ok(true));
+		assert_eq!(store.insert("/h/i/j", 3), Ok(None));
+		assert_counts(&store);
+
+		assert_eq!(store.remove_path("/a/e/f/g"), Ok(Some(2)));
+		assert_counts(&store);
+		assert_eq!(store.remove_subtree("/a/b"), Ok(3));
+		assert_counts(&store);
+		assert_eq!(store.remove_prefix("/h/i").len(), 1);
+		assert_counts(&store);
+
+		assert_eq!(store.add_path("/a/b/c/d", Some(4)), Ok(true));
+		assert_eq!(store.move_subtree("/a/b", "/x/y/b"), Ok(()));
+		assert_counts(&store);
+		assert_eq!(store.rename("/x/y", "z"), Ok(true));
+		assert_counts(&store);
+
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/x/z/b/new/deep", Some(5)), Ok(true));
+		assert_eq!(other.add_path("/m/n", Some(6)), Ok(true));
+		store.merge(other);
+		assert_counts(&store);
+
+		store.retain(|path, _| !path.ends_with("new"));
+		assert_counts(&store);
+		assert_eq!(store.add_path("/empty/dirs/only", None), Ok(true));
+		store.prune();
+		assert_counts(&store);
+		assert_eq!(store.count_under("/x"), 4);
+
+		let mut copy = store.clone();
+		assert_counts(&copy);
+		assert_counts(&store.map_ref(|data| data + 1));
+		assert_counts(&store.subtree("/x").unwrap());
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/x/z/q", Some(7)), Ok(true));
+		assert_counts(&store.union(&other));
+		assert_counts(&store.intersection(&other));
+		assert_counts(&store.difference(&other));
+		let _ = copy.get_or_insert_with("/p/q/r", || 8).unwrap();
+		assert_counts(&copy);
+		copy.remove_subtree("/").unwrap();
+		assert_counts(&copy);
+		assert_counts(&store.map(|data| data * 2));
+	}
+
+	#[test]
+	fn root_store_ancestors() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		let ancestors = |store: &PathStore<i32>, path| store.ancestors(path).map(|a| a.collect::<Vec<_>>());
+		assert_eq!(ancestors(&store, "/a/b/c"), Some(vec![
+			PathBuf::from("/a/b"),
+			PathBuf::from("/a"),
+			PathBuf::from("/"),
+		]));
+		assert_eq!(ancestors(&store, "/a"), Some(vec![PathBuf::from("/")]));
+		assert_eq!(ancestors(&store, "/"), Some(vec![]));
+		assert!(store.ancestors("/a/missing").is_none());
+		assert_eq!(store.ancestors("/a/b/c").unwrap().next(), Some(PathBuf::from("/a/b")));
+
+		// Parent links have to follow nodes that are copied or moved into another store
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/a/x/y", Some(2)), Ok(true));
+		store.merge(other);
+		assert_eq!(store.ancestors("/a/x/y").map(|a| a.count()), Some(3));
+		assert_eq!(ancestors(&store.clone(), "/a/x/y"), ancestors(&store, "/a/x/y"));
+		assert_eq!(ancestors(&store.subtree("/a").unwrap(), "/x/y"), Some(vec![
+			PathBuf::from("/x"),
+			PathBuf::from("/"),
+		]));
+	}
+
+	#[test]
+	fn root_store_parent_path() {
+		let mut store = PathStore::new_case_insensitive(None::<()>);
+
+		assert_eq!(store.add_path("/a/B/c", None), Ok(true));
+		assert_eq!(store.parent_path("/a/B/c"), Some(PathBuf::from("/a/B")));
+		assert_eq!(store.parent_path("/A/b/C"), Some(PathBuf::from("/a/B")));
+		assert_eq!(store.parent_path("/a"), Some(PathBuf::from("/")));
+		assert_eq!(store.parent_path("/"), None);
+		assert_eq!(store.parent_path("/a/missing"), None);
+
+		assert_eq!(store.is_leaf("/a/B/c"), Some(true));
+		assert_eq!(store.is_leaf("/a"), Some(false));
+		assert_eq!(store.is_leaf("/"), Some(false));
+		assert_eq!(store.is_leaf("/a/missing"), None);
+		assert_eq!(PathStore::new(None::<()>).is_leaf("/"), Some(true));
+	}
+
+	#[test]
+	fn root_store_remove_prefix() {
+		let mut store = PathStore::new(Some(0));
+
+		assert_eq!(store.add_path("/f/FDrive", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/sub/hello", Some(3)), Ok(true));
+		assert_eq!(store.add_path("/g", Some(4)), Ok(true));
+
+		let old_size = store.size();
+		let removed_nodes = store.count_under("/f/FDrive") + 1;
+		assert_eq!(store.remove_prefix("/f/FDrive"), vec![
+			(PathBuf::from("/f/FDrive"), 1),
+			(PathBuf::from("/f/FDrive/files"), 2),
+			(PathBuf::from("/f/FDrive/sub/hello"), 3),
+		]);
+		assert_eq!(store.size(), old_size - removed_nodes);
+		assert!(!store.contains("/f/FDrive"));
+		assert!(store.contains("/f"));
+
+		assert_eq!(store.remove_prefix("/f/FDrive"), vec![]);
+		assert_eq!(store.remove_prefix("f"), vec![]);
+
+		assert_eq!(store.remove_prefix("/"), vec![(PathBuf::from("/"), 0), (PathBuf::from("/g"), 4)]);
+		assert_eq!(store.size(), 0);
+		assert!(store.contains("/"));
+		assert!(!store.contains_entry("/"));
+	}
+
+	#[test]
+	fn root_store_depth() {
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.max_depth(), 0);
+		assert_eq!(store.depth("/"), Some(0));
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		assert_eq!(store.max_depth(), 3);
+		assert_eq!(store.depth("/f"), Some(1));
+		assert_eq!(store.depth("/f/FDrive/files"), Some(3));
+		assert_eq!(store.depth("/f/missing"), None);
+	}
+
+	#[test]
+	fn root_store_common_prefix() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.common_prefix(), PathBuf::from("/"));
+
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		assert_eq!(store.common_prefix(), PathBuf::from("/a/b/c"));
+
+		assert_eq!(store.add_path("/a/b/d/e", None), Ok(true));
+		assert_eq!(store.common_prefix(), PathBuf::from("/a/b"));
+
+		store.set_data("/a", Some(2)).unwrap();
+		assert_eq!(store.common_prefix(), PathBuf::from("/a"));
+
+		assert_eq!(store.add_path("/z", None), Ok(true));
+		assert_eq!(store.common_prefix(), PathBuf::from("/"));
+	}
+
+	#[test]
+	fn root_store_longest_existing_prefix() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(
+			store.longest_existing_prefix("/f/FDrive/files/deep/deeper"),
+			(PathBuf::from("/f/FDrive/files"), 3)
+		);
+		assert_eq!(store.longest_existing_prefix("/f/FDrive/files"), (PathBuf::from("/f/FDrive/files"), 3));
+		assert_eq!(store.longest_existing_prefix("/f/other"), (PathBuf::from("/f"), 1));
+		assert_eq!(store.longest_existing_prefix("/g/FDrive"), (PathBuf::from("/"), 0));
+		assert_eq!(store.longest_existing_prefix("/"), (PathBuf::from("/"), 0));
+		assert_eq!(store.longest_existing_prefix("f"), (PathBuf::from("/"), 0));
+	}
+
+	#[test]
+	fn root_store_ancestor_data() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/mnt", Some("mnt")), Ok(true));
+		assert_eq!(store.add_path("/mnt/usb/docs/a.txt", None), Ok(true));
+		assert_eq!(store.add_path("/mnt/usb/docs/b.txt", Some("b")), Ok(true));
+
+		assert_eq!(store.ancestor_data("/mnt/usb/docs/a.txt"), Some((PathBuf::from("/mnt"), "mnt")));
+		assert_eq!(store.ancestor_data("/mnt/usb/docs/b.txt"), Some((PathBuf::from("/mnt/usb/docs/b.txt"), "b")));
+		assert_eq!(store.ancestor_data("/mnt/other/file"), Some((PathBuf::from("/mnt"), "mnt")));
+		assert_eq!(store.ancestor_data("/home"), None);
+
+		store.set_data("/", Some("root")).unwrap();
+		assert_eq!(store.ancestor_data("/home"), Some((PathBuf::from("/"), "root")));
+	}
+
+	#[test]
+	fn root_store_for_each() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/f/a", Some(3)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut paths = Vec::new();
+		let mut total = 0;
+		store.for_each(|path, data| {
+			paths.push(path.to_path_buf());
+			total += data.copied().unwrap_or(0);
+		});
+		assert_eq!(paths, store.walk_all());
+		assert_eq!(total, 5);
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn root_store_par_for_each() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use std::sync::Mutex;
+
+		let mut store = PathStore::new(Some(1));
+		for dir in 0..100 {
+			for file in 0..50 {
+				assert_eq!(store.add_path(format!("/d{}/sub/f{}", dir, file), Some(file)), Ok(true));
+			}
+		}
+
+		let paths = Mutex::new(Vec::new());
+		let total = AtomicUsize::new(0);
+		store.par_for_each(|path, data| {
+			paths.lock().unwrap().push(path.to_path_buf());
+			total.fetch_add(data.copied().unwrap_or(0), Ordering::Relaxed);
+		});
+		let mut paths = paths.into_inner().unwrap();
+		paths.sort();
+		assert_eq!(paths, store.walk_all());
+		assert_eq!(total.into_inner(), 1 + 100 * (0..50).sum::<usize>());
+
+		let mut count = 0;
+		PathStore::new(None::<()>).for_each(|_, _| count += 1);
+		let par_count = AtomicUsize::new(0);
+		PathStore::new(None::<()>).par_for_each(|_, _| {
+			par_count.fetch_add(1, Ordering::Relaxed);
+		});
+		assert_eq!(par_count.into_inner(), count);
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test]
+	fn root_store_par_for_each_narrow() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		// A few long chains, and one that branches at every level, deeper than rayon joins go
+		let mut store = PathStore::new(None);
+		for chain in 0..4 {
+			let mut path = PathBuf::from(format!("/chain{}", chain));
+			for depth in 0..500 {
+				path.push("c");
+				assert_eq!(store.add_path(&path, Some(depth)), Ok(true));
+			}
+		}
+		let mut path = PathBuf::from("/comb");
+		for depth in 0..1000 {
+			assert_eq!(store.add_path(path.join("leaf"), Some(depth)), Ok(true));
+			path.push("d");
+		}
+
+		let mut total = 0;
+		let mut count = 0;
+		store.for_each(|_, data| {
+			total += data.copied().unwrap_or(0);
+			count += 1;
+		});
+		let par_total = AtomicUsize::new(0);
+		let par_count = AtomicUsize::new(0);
+		store.par_for_each(|_, data| {
+			par_total.fetch_add(data.copied().unwrap_or(0), Ordering::Relaxed);
+			par_count.fetch_add(1, Ordering::Relaxed);
+		});
+		assert_eq!(par_count.into_inner(), count);
+		assert_eq!(par_total.into_inner(), total);
+
+		// A panic in f reaches the caller rather than leaving the other threads waiting
+		let caught = std::panic::catch_unwind(|| {
+			store.par_for_each(|path, _| assert!(!path.ends_with("chain2/c/c")));
+		});
+		assert!(caught.is_err());
+	}
+
+	#[test]
+	fn root_store_filter_walk() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", None), Ok(true));
+		assert_eq!(store.add_path("/f/a", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/a/b", None), Ok(true));
+		assert_eq!(store.add_path("/g/files", None), Ok(true));
+
+		let mut asked = Vec::new();
+		let walk = store.filter_walk(|path| {
+			asked.push(path.to_path_buf());
+			path.starts_with("/f") && !path.ends_with("FDrive")
+		});
+		assert_eq!(walk, vec![PathBuf::from("/f/a"), PathBuf::from("/f/a/b")]);
+		assert_eq!(asked, vec![
+			PathBuf::from("/f"),
+			PathBuf::from("/f/FDrive"),
+			PathBuf::from("/f/a"),
+			PathBuf::from("/f/a/b"),
+			PathBuf::from("/g"),
+		]);
+
+		assert_eq!(store.filter_walk(|_| true), store.walk());
+		assert_eq!(store.filter_walk(|_| false), Vec::<PathBuf>::new());
+	}
+
+	#[test]
+	fn root_store_map() {
+		let mut store = PathStore::new(Some(1u64));
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1024)), Ok(true));
+		assert_eq!(store.add_path("/f/a", None), Ok(true));
+
+		let kib = store.map_ref(|size| size / 1024);
+		assert_eq!(kib.walk_with_data(), vec![(PathBuf::from("/"), 0), (PathBuf::from("/f/FDrive/files"), 1)]);
+		assert_eq!(kib.size(), store.size());
+		assert_eq!(kib.walk_all(), store.walk_all());
+		assert_eq!(kib.ancestors("/f/FDrive/files").map(|a| a.count()), Some(3));
+
+		let mut calls = 0;
+		let strings = store.map(|size| {
+			calls += 1;
+			size.to_string()
+		});
+		assert_eq!(calls, 2);
+		assert_eq!(strings.get_cloned("/f/FDrive/files"), Some("1024".to_owned()));
+		assert_eq!(strings.get_cloned("/f/a"), None);
+		assert_eq!(strings.size(), 4);
+		assert_eq!(strings.ancestors("/f/FDrive/files").map(|a| a.count()), Some(3));
+	}
+
+	#[test]
+	fn root_store_count_nodes() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.count_leaves(), 1);
+		assert_eq!(store.count_data_nodes(), 0);
+
+		// Implicit parents count towards size but are not leaves
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.size(), 3);
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f/a", Some(2)), Ok(true));
+		assert_eq!(store.insert("/f", 3), Ok(None));
+		assert_eq!(store.size(), 5);
+		assert_eq!(store.count_leaves(), 3);
+		assert_eq!(store.count_data_nodes(), 3);
+
+		store.set_data("/", Some(0)).unwrap();
+		assert_eq!(store.count_data_nodes(), 4);
+		assert_eq!(store.count_leaves(), 3);
+	}
+
+	#[test]
+	fn root_store_size_invariant() {
+		let mut store = PathStore::new(None);
+		assert_size_consistent(&store);
+
+		let paths = ["/f/FDrive/files", "/f/FDrive/hello", "/f/a", "/g", "/f/FDrive", "/h/i/j/k"];
+		for path in paths.iter() {
+			store.add_path(path, None).unwrap();
+			assert_size_consistent(&store);
+		}
+		// Re-adding existing paths, with or without data, never creates nodes
+		for (i, path) in paths.iter().enumerate() {
+			let size = store.size();
+			store.add_path(path, Some(i)).unwrap();
+			store.insert(path, i).unwrap();
+			*store.entry(path).unwrap().or_insert(0) += 1;
+			assert_eq!(store.size(), size);
+		}
+		assert_eq!(store.size(), 10);
+
+		store.remove_path("/f/FDrive/hello").unwrap();
+		assert_size_consistent(&store);
+		store.remove_path("/h/i/j/k").unwrap();
+		assert_size_consistent(&store);
+		store.remove_path("/missing").unwrap();
+		assert_size_consistent(&store);
+
+		let mut other = PathStore::new(None);
+		other.add_path("/f/FDrive/other/deep", Some(1)).unwrap();
+		other.add_path("/x/y", None).unwrap();
+		store.merge(other);
+		assert_size_consistent(&store);
+
+		store.prune();
+		assert_size_consistent(&store);
+		store.remove_prefix("/f/FDrive/other");
+		assert_size_consistent(&store);
+		store.remove_subtree("/f/a").unwrap();
+		assert_size_consistent(&store);
+		assert_size_consistent(&store.clone());
+		assert_size_consistent(&store.map_ref(|_| ()));
+		assert_size_consistent(&store.subtree("/f").unwrap());
+
+		store.remove_subtree("/").unwrap();
+		assert_size_consistent(&store);
+		assert_eq!(store.size(), 0);
+	}
+
+	#[test]
+	fn root_store_contains_prefix() {
+		let mut store = PathStore::new(None::<()>);
+
+		assert_eq!(store.add_path("/foo/bar/baz", None), Ok(true));
+		assert!(store.contains_prefix("/"));
+		assert!(store.contains_prefix("/foo"));
+		assert!(store.contains_prefix("/foo/bar/baz"));
+		assert!(!store.contains_prefix("/f"));
+		assert!(!store.contains_prefix("/foo/bar/baz/qux"));
+		assert!(!store.contains_prefix("foo"));
+		assert_eq!(store.count_under("/foo"), 2);
+		assert_eq!(store.count_under("/f"), 0);
+	}
+
+	#[test]
+	fn root_store_case_insensitive() {
+		let mut store = PathStore::new_case_insensitive(None);
+
+		assert_eq!(store.add_path("/Users/Me/Foo.txt", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/users/me/FOO.TXT", None), Ok(false));
+		assert_eq!(store.insert("/USERS/ME/foo.txt", 2), Ok(Some(1)));
+		assert_eq!(store.size(), 3);
+		assert_eq!(store.walk(), vec![PathBuf::from("/Users/Me/Foo.txt")]);
+
+		assert!(store.contains("/users/ME"));
+		assert_eq!(store.get_cloned("/users/me/foo.TXT"), Some(2));
+		assert_eq!(store.longest_existing_prefix("/users/me/bar"), (PathBuf::from("/Users/Me"), 2));
+		assert_eq!(store.walk_under("/users"), Ok(vec![PathBuf::from("/Users/Me/Foo.txt")]));
+		assert_eq!(store.ancestors("/users/me").unwrap().next(), Some(PathBuf::from("/Users")));
+
+		assert_eq!(store.add_path("/users/Other", None), Ok(true));
+		assert_eq!(store.remove_path("/USERS/OTHER"), Ok(None));
+		assert!(!store.contains("/Users/Other"));
+		assert_eq!(store.remove_prefix("/users/me"), vec![(PathBuf::from("/Users/Me/Foo.txt"), 2)]);
+		assert_eq!(store.size(), 1);
+
+		// Normal stores keep names that differ in case apart
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path("/Foo", None), Ok(true));
+		assert_eq!(store.add_path("/foo", None), Ok(true));
+		assert_eq!(store.size(), 2);
+		assert!(!store.contains("/FOO"));
+	}
+
+	#[test]
+	fn root_store_case_insensitive_prefix_queries() {
+		let mut store = PathStore::new_case_insensitive(None);
+
+		assert_eq!(store.add_path("/Foo", None), Ok(true));
+		assert_eq!(store.add_path("/foo/bar", Some(1)), Ok(true));
+		assert_eq!(store.size(), 2);
+		assert_eq!(store.walk(), vec![PathBuf::from("/Foo/bar")]);
+
+		assert_eq!(store.count_under("/FOO"), 1);
+		assert_eq!(store.children("/fOO"), Some(vec![OsString::from("bar")]));
+		assert_eq!(store.paths_under("/foo"), vec![PathBuf::from("/Foo/bar")]);
+		assert_eq!(store.longest_prefix("/FOO/BAR/baz"), Some(PathBuf::from("/Foo/bar")));
+		assert_eq!(store.depth("/FOO/Bar"), Some(2));
+		assert_eq!(store.is_leaf("/foo/BAR"), Some(true));
+		assert_eq!(store.subtree("/foo").unwrap().get_cloned("/BAR"), Some(1));
+
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/FOO/baz", Some(2)), Ok(true));
+		store.merge(other);
+		assert_eq!(store.walk(), vec![PathBuf::from("/Foo/bar"), PathBuf::from("/Foo/baz")]);
+
+		assert_eq!(store.remove_subtree("/FOO/BAR"), Ok(1));
+		assert_eq!(store.remove_prefix("/foo"), vec![(PathBuf::from("/Foo/baz"), 2)]);
+		assert_eq!(store.size(), 0);
+	}
+
+	#[test]
+	fn root_store_case_insensitive_many_siblings() {
+		let mut store = PathStore::new_case_insensitive(None);
+		let count = 5_000;
+		for i in 0..count {
+			assert_eq!(store.add_path(format!("/d/File{}", i), Some(i)), Ok(true));
+		}
+		for i in 0..count {
+			assert_eq!(store.add_path(format!("/D/file{}", i), None), Ok(false));
+		}
+		assert_eq!(store.size(), count + 1);
+		assert_eq!(store.get_cloned("/d/FILE4321"), Some(4321));
+		assert_eq!(store.complete("/d/file499").len(), 11);
+
+		// The lookup index has to follow every way children are removed, renamed or copied
+		assert_eq!(store.rename("/d/file1", "Renamed"), Ok(true));
+		assert!(!store.contains("/d/FILE1"));
+		assert_eq!(store.get_cloned("/d/RENAMED"), Some(1));
+		store.retain(|path, _| path.as_os_str().len() < 9);
+		assert_eq!(store.size(), 10);
+		assert!(!store.contains("/d/file10"));
+		assert!(store.contains("/d/FILE9"));
+		assert_eq!(store.add_path("/d/FILE10", None), Ok(true));
+
+		let copy = store.clone().map(|data| data * 2);
+		assert_eq!(copy.get_cloned("/D/file3"), Some(6));
+		let mut other = PathStore::new(None);
+		assert_eq!(other.add_path("/e/Deep/Name", Some(0)), Ok(true));
+		let union = store.union(&other);
+		assert_eq!(union.get_cloned("/E/deep/NAME"), Some(0));
+		assert!(union.difference(&other).contains("/D/File3"));
+		assert!(!store.intersection(&union).contains("/e/deep"));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn root_store_case_insensitive_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new_case_insensitive(None::<()>);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/A\xff"), None), Ok(true));
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/a\xff"), None), Ok(false));
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/a\xfe"), None), Ok(true));
+		assert!(store.contains(OsStr::from_bytes(b"/a\xFF")));
+		assert_eq!(store.walk(), vec![PathBuf::from(OsStr::from_bytes(b"/A\xff")), PathBuf::from(OsStr::from_bytes(b"/a\xfe"))]);
+	}
+
+	#[test]
+	fn root_store_case_insensitive_unicode() {
+		let mut store = PathStore::new_case_insensitive(None::<()>);
+		assert_eq!(store.add_path("/Ärger", None), Ok(true));
+		assert_eq!(store.add_path("/ÄRGER", None), Ok(false));
+		// Only folded with the unicode-case feature
+		assert_eq!(store.contains("/ärger"), cfg!(feature = "unicode-case"));
+		assert!(!store.contains("/aerger"));
+	}
+
+	#[test]
+	fn prefix_names() {
+		let name = |prefix| prefix_name(prefix).into_string().unwrap();
+
+		assert_eq!(name(Prefix::Disk(b'c')), r"C:\");
+		assert_eq!(name(Prefix::VerbatimDisk(b'C')), r"C:\");
+		assert_eq!(name(Prefix::UNC(OsStr::new("server"), OsStr::new("share"))), r"\\server\share\");
+		assert_eq!(name(Prefix::VerbatimUNC(OsStr::new("server"), OsStr::new("share"))), r"\\server\share\");
+		assert_eq!(name(Prefix::Verbatim(OsStr::new("pipe"))), r"\\?\pipe\");
+		assert_eq!(name(Prefix::DeviceNS(OsStr::new("COM1"))), r"\\.\COM1\");
+	}
+
+	#[test]
+	fn node_names_skip_root() {
+		assert_eq!(node_names(Path::new("/")), Vec::<&OsStr>::new());
+		assert_eq!(node_names(Path::new("/a/b")), vec![OsStr::new("a"), OsStr::new("b")]);
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn root_store_windows_prefixes() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path(r"C:\foo", Some(1)), Ok(true));
+		assert_eq!(store.add_path(r"\\?\C:\foo", Some(2)), Ok(false));
+		assert_eq!(store.add_path(r"c:\foo\bar", None), Ok(true));
+		assert_eq!(store.add_path(r"\\server\share\x", None), Ok(true));
+		assert_eq!(store.add_path(r"\\?\UNC\server\share\y", None), Ok(true));
+		assert_eq!(store.size(), 6);
+		assert_eq!(store.get_cloned(r"C:\foo"), Some(2));
+		assert_eq!(store.depth(r"C:\foo\bar"), Some(2));
+
+		assert_eq!(store.walk(), vec![
+			PathBuf::from(r"C:\foo\bar"),
+			PathBuf::from(r"\\server\share\x"),
+			PathBuf::from(r"\\server\share\y"),
+		]);
+		assert!(store.walk().iter().all(|path| path.is_absolute()));
+		assert_eq!(store.remove_subtree(r"C:\"), Ok(3));
+		assert!(store.contains(r"\\server\share\x"));
+	}
+
+	#[test]
+	fn root_store_push_parent_dir() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b/../c", Some(1)), Ok(true));
+		assert_eq!(store.walk(), vec![PathBuf::from("/a/c")]);
+		assert_eq!(store.add_path("/a/./c", None), Ok(false));
+		assert_eq!(store.add_path("/a/c/d/../..", Some(2)), Ok(false));
+		assert_eq!(store.get_cloned("/a"), Some(2));
+		assert_eq!(store.size(), 2);
+
+		assert_eq!(store.add_path("/..", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/..") }));
+		assert_eq!(store.size(), 2);
+	}
+
+	#[test]
+	fn root_store_escapes_root() {
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path("/etc/passwd", Some(1)), Ok(true));
+
+		// At the start
+		assert_eq!(store.add_path("/../etc/passwd", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/../etc/passwd") }));
+		// In the middle, after a prefix that is in the store
+		assert_eq!(store.add_path("/etc/../../etc/passwd", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/etc/../../etc/passwd") }));
+		assert_eq!(store.add_path("/etc/passwd/../../..", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/etc/passwd/../../..") }));
+		assert_eq!(store.size(), 2);
+
+		// Exactly balancing out is the root itself
+		assert_eq!(store.add_path("/etc/passwd/../..", Some(0)), Ok(false));
+		assert_eq!(store.get_cloned("/etc/.."), Some(0));
+
+		// Lookups never alias to the children of the root
+		assert!(!store.contains("/../etc/passwd"));
+		assert_eq!(store.get_cloned("/etc/../../etc/passwd"), None);
+		assert_eq!(store.children("/.."), None);
+		assert_eq!(store.walk_under("/../etc"), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/../etc") }));
+		assert_eq!(store.set_data("/../etc/passwd", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/../etc/passwd") }));
+		assert_eq!(store.remove_path("/../etc/passwd"), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/../etc/passwd") }));
+		assert_eq!(store.remove_subtree("/etc/../.."), Err(StorageError::PathEscapesRoot { path: PathBuf::from("/etc/../..") }));
+		assert_eq!(store.get_cloned("/etc/passwd"), Some(1));
+		assert_eq!(store.size(), 2);
+	}
+
+	#[test]
+	fn root_store_separators() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a//b/", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b", None), Ok(false));
+		assert_eq!(store.add_path("//a///b//", Some(2)), Ok(false));
+		assert_eq!(store.size(), 2);
+		assert_eq!(store.walk(), vec![PathBuf::from("/a/b")]);
+		assert_eq!(store.walk_all(), vec![PathBuf::from("/"), PathBuf::from("/a"), PathBuf::from("/a/b")]);
+
+		assert!(store.contains("/a//b/"));
+		assert_eq!(store.get_cloned("/a/b//"), Some(2));
+		assert_eq!(store.children("/a/"), Some(vec![OsString::from("b")]));
+		assert_eq!(store.depth("/a//b"), Some(2));
+
+		// The bare root is never a new path, only its data is set
+		assert_eq!(store.add_path("/", None), Ok(false));
+		assert_eq!(store.get_cloned("/"), None);
+		assert_eq!(store.add_path("/", Some(0)), Ok(false));
+		assert_eq!(store.add_path("///", Some(3)), Ok(false));
+		assert_eq!(store.get_cloned("/"), Some(3));
+		assert_eq!(store.size(), 2);
+		assert_eq!(store.walk(), vec![PathBuf::from("/"), PathBuf::from("/a/b")]);
+
+		assert_eq!(store.remove_subtree("/a//b/"), Ok(1));
+		assert_eq!(store.remove_subtree("/a//"), Ok(1));
+		assert_eq!(store.size(), 0);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn root_store_separators_raw_bytes() {
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/a//b\xff/"), None), Ok(true));
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/a/b\xff"), None), Ok(false));
+		assert_eq!(store.size(), 2);
+		assert_eq!(store.walk(), vec![PathBuf::from(OsStr::from_bytes(b"/a/b\xff"))]);
+		assert!(store.iter_all().all(|path| path.components().all(|c| !c.as_os_str().is_empty())));
+	}
+
+	#[test]
+	fn root_store_normalized_lookups() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/./b/../c", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/c", Some(2)), Ok(false));
+		assert_eq!(store.add_path("/a/b/x", None), Ok(true));
+		assert_eq!(store.size(), 4);
+		assert_size_consistent(&store);
+
+		// Every lookup agrees on where /a/c is
+		assert!(store.contains("/a/b/../c"));
+		assert!(store.contains("/a/./c/."));
+		assert_eq!(store.get_cloned("/a/b/x/../../c"), Some(2));
+		assert_eq!(store.depth("/a/b/../c"), Some(2));
+		assert_eq!(store.children("/a/b/.."), Some(vec![OsString::from("b"), OsString::from("c")]));
+		assert_eq!(store.walk_under("/a/b/x/.."), Ok(vec![PathBuf::from("/a/b/x")]));
+		assert!(!store.contains("/a/b/c"));
+
+		assert_eq!(store.remove_path("/a/b/../c"), Ok(Some(2)));
+		assert!(!store.contains("/a/c"));
+		assert_eq!(store.remove_subtree("/a/./b/x/.."), Ok(2));
+		assert_eq!(store.size(), 1);
+		assert_size_consistent(&store);
+	}
+
+	#[test]
+	fn root_store_invalid_component() {
+		let mut store = PathStore::new(None::<()>);
+
+		let nul = StorageError::InvalidComponent {
+			component: OsString::from("b\0c"),
+			reason: InvalidComponentReason::ContainsNul,
+		};
+		assert_eq!(store.add_path("/a/b\0c", None), Err(nul.clone()));
+		assert_eq!(store.insert("/a/b\0c/d", ()), Err(nul.clone()));
+		assert!(store.entry("/a/b\0c").is_err());
+		assert_eq!(store.size(), 0);
+		assert_eq!(nul.to_string(), "Name \"b\\0c\" can not be stored: name contains a NUL byte");
+
+		let error = store.walk_under("/missing").unwrap_err();
+		assert_eq!(error.to_string(), "Input path /missing is not in the store");
+	}
+
+	#[test]
+	fn root_store_rename() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b/c", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/x", None), Ok(true));
+		assert_eq!(store.rename("/a/b", "d"), Ok(true));
+		assert!(!store.contains("/a/b"));
+		assert_eq!(store.get_cloned("/a/d/c"), Some(1));
+		assert_eq!(store.parent_path("/a/d/c"), Some(PathBuf::from("/a/d")));
+		assert_eq!(store.size(), 4);
+		assert_size_consistent(&store);
+
+		assert_eq!(store.rename("/a/d", "x"), Err(StorageError::TargetExists { path: PathBuf::from("/a/x") }));
+		assert_eq!(store.rename("/a/d", "d"), Ok(true));
+		assert_eq!(store.rename("/a/missing", "y"), Ok(false));
+		assert_eq!(store.rename("/", "y"), Ok(false));
+		assert_eq!(store.rename("/a/d", "y/z"), Err(StorageError::InvalidComponent { component: OsString::from("y/z"), reason: InvalidComponentReason::ContainsSeparator }));
+		assert_eq!(store.rename("/a/d", ".."), Err(StorageError::InvalidComponent { component: OsString::from(".."), reason: InvalidComponentReason::Reserved }));
+		assert_eq!(store.rename("/a/d", ""), Err(StorageError::InvalidComponent { component: OsString::new(), reason: InvalidComponentReason::Empty }));
+		assert_eq!(store.rename("a/d", "y"), Err(StorageError::PathNotAbsolute { path: PathBuf::from("a/d") }));
+		assert_eq!(store.walk(), vec![PathBuf::from("/a/d/c"), PathBuf::from("/a/x")]);
+
+		// Ignoring case a node can change case, but not take the name of a sibling
+		let mut store = PathStore::new_case_insensitive(None::<()>);
+		assert_eq!(store.add_path("/foo", None), Ok(true));
+		assert_eq!(store.add_path("/bar", None), Ok(true));
+		assert_eq!(store.rename("/FOO", "Foo"), Ok(true));
+		assert_eq!(store.walk(), vec![PathBuf::from("/Foo"), PathBuf::from("/bar")]);
+		assert_eq!(store.rename("/foo", "BAR"), Err(StorageError::TargetExists { path: PathBuf::from("/BAR") }));
+	}
+
+	#[test]
+	fn root_store_move_subtree() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/a/b/x", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/a/b/y", None), Ok(true));
+		assert_eq!(store.add_path("/a/e", None), Ok(true));
+		assert_eq!(store.move_subtree("/a/b", "/c/d"), Ok(()));
+		assert_eq!(store.walk(), vec![PathBuf::from("/a/e"), PathBuf::from("/c/d/x"), PathBuf::from("/c/d/y")]);
+		assert_eq!(store.get_cloned("/c/d/x"), Some(1));
+		assert_eq!(store.size(), 6);
+		assert_size_consistent(&store);
+		assert_eq!(store.ancestors("/c/d/x").unwrap().collect::<Vec<_>>(), vec![
+			PathBuf::from("/c/d"),
+			PathBuf::from("/c"),
+			PathBuf::from("/"),
+		]);
+
+		assert_eq!(store.move_subtree("/c/d", "/a/e"), Err(StorageError::TargetExists { path: PathBuf::from("/a/e") }));
+		assert_eq!(store.move_subtree("/c/d", "/c/d"), Err(StorageError::TargetExists { path: PathBuf::from("/c/d") }));
+		assert_eq!(store.move_subtree("/c", "/c/d/z/w"), Err(StorageError::MoveIntoDescendant { from: PathBuf::from("/c"), to: PathBuf::from("/c/d/z/w") }));
+		assert_eq!(store.move_subtree("/", "/z"), Err(StorageError::MoveIntoDescendant { from: PathBuf::from("/"), to: PathBuf::from("/z") }));
+		assert_eq!(store.move_subtree("/missing", "/z"), Err(StorageError::PathNotFound { path: PathBuf::from("/missing") }));
+		assert_eq!(store.move_subtree("c", "/z"), Err(StorageError::PathNotAbsolute { path: PathBuf::from("c") }));
+		assert_eq!(store.move_subtree("/c/d", "/a\0b/x"), Err(StorageError::InvalidComponent {
+			component: OsString::from("a\0b"),
+			reason: InvalidComponentReason::ContainsNul,
+		}));
+		assert!(store.contains("/c/d"));
+		assert_eq!(store.size(), 6);
+
+		// Moving up next to the old parent
+		assert_eq!(store.move_subtree("/c/d", "/d"), Ok(()));
+		assert_eq!(store.parent_path("/d/x"), Some(PathBuf::from("/d")));
+		assert_eq!(store.children("/c"), Some(vec![]));
+		assert_eq!(store.size(), 6);
+		assert_size_consistent(&store);
+	}
+
+	#[test]
+	fn root_store_retain() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f/a/b", Some(2)), Ok(true));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+
+		let mut seen = Vec::new();
+		store.retain(|path, _| {
+			seen.push(path.to_path_buf());
+			path != Path::new("/f/a")
+		});
+		// Nothing under /f/a is asked about
+		assert!(!seen.contains(&PathBuf::from("/f/a/b")));
+		assert!(!store.contains("/f/a"));
+		assert_eq!(store.size(), 5);
+
+		store.retain(|path, data| data.is_some() || path.starts_with("/f") && !path.ends_with("hello"));
+		assert_eq!(store.walk(), vec![PathBuf::from("/f/FDrive/files")]);
+		assert_eq!(store.size(), 3);
+
+		store.retain(|_, _| false);
+		assert!(store.is_empty());
+	}
+
+	#[test]
+	fn root_store_find_where() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_path("/f/FDrive/files", Some(2019)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", Some(2021)), Ok(true));
+		assert_eq!(store.add_path("/f/a", None), Ok(true));
+		assert_eq!(store.add_path("/g", Some(2023)), Ok(true));
+
+		assert_eq!(store.find_where(|year| *year > 2020), vec![
+			PathBuf::from("/f/FDrive/hello"),
+			PathBuf::from("/g"),
+		]);
+		assert!(store.find_where(|year| *year > 2030).is_empty());
+
+		let checked = std::cell::Cell::new(0);
+		assert!(store.any_where(|year| {
+			checked.set(checked.get() + 1);
+			*year > 2020
+		}));
+		assert_eq!(checked.get(), 2);
+		assert!(!store.any_where(|year| *year < 2000));
+		assert!(store.all_where(|year| *year > 2000));
+		assert!(!store.all_where(|year| *year > 2020));
+		assert!(PathStore::new(None::<i32>).all_where(|_| false));
+	}
+
+	#[test]
+	fn root_store_add_str() {
+		let mut store = PathStore::new(None);
+
+		assert_eq!(store.add_str("a.b.c", ".", Some(1)), Ok(true));
+		assert_eq!(store.add_str("a.d", ".", None), Ok(true));
+		assert_eq!(store.add_str("a..b.", ".", Some(2)), Ok(false));
+		assert_eq!(store.size(), 4);
+		assert_eq!(store.get_cloned("/a/b"), Some(2));
+		assert_eq!(store.walk_str("."), vec!["a.b", "a.b.c", "a.d"]);
+
+		// A name with a separator in it would look like two names to every walk
+		assert_eq!(store.add_str("x/y.z", ".", None), Err(StorageError::InvalidComponent {
+			component: OsString::from("x/y"),
+			reason: InvalidComponentReason::ContainsSeparator,
+		}));
+		assert_eq!(store.add_str("x.y/z", ".", None).map_err(|_| ()), Err(()));
+		assert_eq!(store.add_str("x/../y", "/", None).map_err(|_| ()), Err(()));
+		assert_eq!(store.add_str("x.y", ".", None), Ok(true));
+		assert_eq!(store.size(), 6);
+		assert_eq!(store.walk(), vec![
+			PathBuf::from("/a/b"),
+			PathBuf::from("/a/b/c"),
+			PathBuf::from("/a/d"),
+			PathBuf::from("/x/y"),
+		]);
+		assert_eq!(store.fuzzy_find("xy", 10).len(), 1);
+
+		let mut store = PathStore::new(None::<()>);
+		assert_eq!(store.add_str("std::collections::BTreeMap", "::", None), Ok(true));
+		assert_eq!(store.add_str("std::sync", "::", None), Ok(true));
+		assert_eq!(store.walk_str("::"), vec!["std::collections::BTreeMap", "std::sync"]);
+		assert_eq!(store.walk_str("/"), vec!["std/collections/BTreeMap", "std/sync"]);
+		assert_eq!(PathStore::new(None::<()>).walk_str("."), vec![""]);
+	}
+
+	#[test]
+	fn root_store_relative() {
+		let mut store = PathStore::new_relative(None);
+
+		assert_eq!(store.add_path("foo/bar", Some(1)), Ok(true));
+		assert_eq!(store.add_path("foo/./baz/../qux", None), Ok(true));
+		assert_eq!(store.add_path("/foo", None), Err(StorageError::PathNotRelative { path: PathBuf::from("/foo") }));
+		assert_eq!(store.add_path("..", None), Err(StorageError::PathEscapesRoot { path: PathBuf::from("..") }));
+		assert_eq!(store.size(), 3);
+
+		assert_eq!(store.walk(), vec![PathBuf::from("foo/bar"), PathBuf::from("foo/qux")]);
+		assert_eq!(store.walk_all()[0], PathBuf::new());
+		assert_eq!(store.walk_under("foo"), Ok(store.walk()));
+		assert_eq!(store.walk_with_data(), vec![(PathBuf::from("foo/bar"), 1)]);
+		assert_eq!(store.glob("foo/q*"), vec![PathBuf::from("foo/qux")]);
+		assert!(store.glob("/foo/*").is_empty());
+		assert_eq!(store.ancestors("foo/bar").unwrap().collect::<Vec<_>>(), vec![PathBuf::from("foo"), PathBuf::new()]);
+		assert!(store.contains("foo"));
+		assert!(store.contains(""));
+		assert!(!store.contains("/foo"));
+		assert_eq!(store.into_iter().collect::<Vec<_>>(), vec![(PathBuf::from("foo/bar"), 1)]);
 	}
 }
@@ -0,0 +1,65 @@
+use crate::iter::Walker;
+use crate::{PathNodeRef, PathStore};
+
+use std::path::{Path, PathBuf};
+
+/// Nested joins before the rest of a subtree is walked on the thread that reached it, each join
+/// keeps a few frames on the stack of the thread running it
+const MAX_JOINS: usize = 256;
+
+impl<T> PathStore<T> {
+	/// Same as `for_each` but spread over a thread per core, so nodes are visited in no particular order
+	///
+	/// Every branch point splits its children into two halves with `rayon::join`, and chains of
+	/// nodes with a single child are followed without splitting. Past a few hundred nested joins
+	/// the rest of a subtree is walked on one thread, so very deep trees don't run out of stack.
+	/// Returns once every node has been visited
+	pub fn par_for_each<F>(&self, f: F)
+	where
+		F: Fn(&Path, Option<&T>) + Sync,
+		T: Send + Sync,
+	{
+		par_walk(self.root_path(), self.root.clone(), &f, 0);
+	}
+}
+
+fn par_walk<T, F>(mut path: PathBuf, mut node: PathNodeRef<T>, f: &F, joins: usize)
+where
+	F: Fn(&Path, Option<&T>) + Sync,
+	T: Send + Sync,
+{
+	loop {
+		let mut children: Vec<_> = {
+			let node = node.read().expect("Failed to lock tree node when walking tree");
+			f(&path, node.data.as_ref());
+			node.items.iter().map(|(name, child)| (path.join(name), child.clone())).collect()
+		};
+		match children.len() {
+			0 => return,
+			1 => (path, node) = children.pop().expect("Checked above"),
+			_ if joins >= MAX_JOINS => {
+				for (path, child) in children {
+					Walker::starting_at(path, child, usize::MAX).next_with(|path, _, node| {
+						f(path, node.data.as_ref());
+						None::<()>
+					});
+				}
+				return;
+			}
+			_ => return par_split(children, f, joins),
+		}
+	}
+}
+
+fn par_split<T, F>(mut children: Vec<(PathBuf, PathNodeRef<T>)>, f: &F, joins: usize)
+where
+	F: Fn(&Path, Option<&T>) + Sync,
+	T: Send + Sync,
+{
+	if children.len() == 1 {
+		let (path, child) = children.pop().expect("Checked above");
+		return par_walk(path, child, f, joins);
+	}
+	let right = children.split_off(children.len() / 2);
+	rayon::join(|| par_split(children, f, joins + 1), || par_split(right, f, joins + 1));
+}
@@ -0,0 +1,354 @@
+use crate::{check_name, PathNode, PathNodeRef, PathStore};
+
+use serde::de::{Deserialize, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeStruct, Serializer};
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+
+const ROOT_FIELDS: &[&str] = &["data", "children"];
+const NODE_FIELDS: &[&str] = &["name", "data", "children"];
+
+/// Deepest node that is written, each level below the root takes two levels of nesting and
+/// `serde_json` reads back no more than 128
+const MAX_DEPTH: usize = 62;
+
+/// Written as the tree itself, the root as `{ data, children }` and every other node as
+/// `{ name, data, children }` with its children in sorted order
+///
+/// Shared prefixes are only written once. Names use the serde form of `OsString`, which keeps
+/// names that are not valid UTF-8 but is tied to the platform, a store written on Unix can only
+/// be read back on Unix. Only the paths and data are written, not settings like the base
+///
+/// Nesting follows the tree, and `serde_json` stops reading at 128 levels of nesting, which is
+/// 62 levels of the tree. So that nothing is written that can't be read back, a node more than 62
+/// levels below the root is an error. `serde_flat` writes a list of paths instead, which has no
+/// such limit
+impl<T: Serialize> Serialize for PathStore<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		NodeRef { name: None, node: &self.root, depth: 0 }.serialize(serializer)
+	}
+}
+
+/// Rebuilds the nodes, parent links and `size` from the tree
+///
+/// The store has the default settings. Names `add_path` would reject and names that appear twice
+/// under the same node are errors
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for PathStore<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let root = deserializer.deserialize_struct("PathStore", ROOT_FIELDS, NodeVisitor::new(false))?;
+		Ok(PathStore {
+			root: root.node,
+			size: root.descendants,
+			base: None,
+			case_insensitive: false,
+			relative: false,
+		})
+	}
+}
+
+/// Node to write, name is None for the root
+struct NodeRef<'a, T> {
+	name: Option<&'a OsStr>,
+	node: &'a PathNodeRef<T>,
+	/// Levels below the root
+	depth: usize,
+}
+
+impl<'a, T: Serialize> Serialize for NodeRef<'a, T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		if self.depth > MAX_DEPTH {
+			let message = format!("Tree is deeper than the {} levels that can be read back", MAX_DEPTH);
+			return Err(ser::Error::custom(message));
+		}
+		let node = self.node.read().expect("Failed to lock tree node when serializing tree");
+		let mut state = match self.name {
+			Some(name) => {
+				let mut state = serializer.serialize_struct("Node", NODE_FIELDS.len())?;
+				state.serialize_field("name", name)?;
+				state
+			}
+			None => serializer.serialize_struct("PathStore", ROOT_FIELDS.len())?,
+		};
+		state.serialize_field("data", &node.data)?;
+		state.serialize_field("children", &Children(&node.items, self.depth + 1))?;
+		state.end()
+	}
+}
+
+/// Children to write and their depth
+struct Children<'a, T>(&'a BTreeMap<OsString, PathNodeRef<T>>, usize);
+
+impl<'a, T: Serialize> Serialize for Children<'a, T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let depth = self.1;
+		serializer.collect_seq(self.0.iter().map(|(name, node)| NodeRef { name: Some(name), node, depth }))
+	}
+}
+
+/// Node that was read back, along with the number of nodes under it
+struct Node<T> {
+	name: Option<OsString>,
+	node: PathNodeRef<T>,
+	descendants: usize,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Node<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_struct("Node", NODE_FIELDS, NodeVisitor::new(true))
+	}
+}
+
+struct NodeVisitor<T> {
+	/// False for the root, which has no name
+	named: bool,
+	_data: PhantomData<T>,
+}
+
+impl<T> NodeVisitor<T> {
+	fn new(named: bool) -> Self {
+		Self { named, _data: PhantomData }
+	}
+
+	/// Link children under a new node with data
+	fn build<E: Error>(self, name: Option<OsString>, data: Option<T>, children: Vec<Node<T>>) -> Result<Node<T>, E> {
+		if self.named && name.is_none() {
+			return Err(E::missing_field("name"));
+		}
+		let node = Arc::new(RwLock::new(PathNode::new(data)));
+		let mut descendants = 0;
+		{
+			let mut node_lock = node.write().unwrap();
+			for child in children {
+				let name = child.name.expect("Children are always named");
+				check_name(&name).map_err(E::custom)?;
+				child.node.write().unwrap().parent = Some(Arc::downgrade(&node));
+				descendants += 1 + child.descendants;
+				if node_lock.items.insert(name, child.node).is_some() {
+					return Err(E::custom("Name appears twice under the same node"));
+				}
+			}
+			node_lock.descendants = descendants;
+		}
+		Ok(Node { name, node, descendants })
+	}
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for NodeVisitor<T> {
+	type Value = Node<T>;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a path tree node")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node<T>, A::Error> {
+		let missing = |index| A::Error::invalid_length(index, &"the fields of a path tree node");
+		let name = if self.named { Some(seq.next_element()?.ok_or_else(|| missing(0))?) } else { None };
+		let offset = self.named as usize;
+		let data = seq.next_element()?.ok_or_else(|| missing(offset))?;
+		let children = seq.next_element()?.ok_or_else(|| missing(offset + 1))?;
+		self.build(name, data, children)
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node<T>, A::Error> {
+		let (mut name, mut data, mut children) = (None, None, None);
+		while let Some(field) = map.next_key::<Field>()? {
+			match field {
+				Field::Name if self.named && name.is_none() => name = Some(map.next_value()?),
+				Field::Data if data.is_none() => data = Some(map.next_value()?),
+				Field::Children if children.is_none() => children = Some(map.next_value()?),
+				Field::Name if !self.named => return Err(A::Error::unknown_field("name", ROOT_FIELDS)),
+				Field::Name => return Err(A::Error::duplicate_field("name")),
+				Field::Data => return Err(A::Error::duplicate_field("data")),
+				Field::Children => return Err(A::Error::duplicate_field("children")),
+			}
+		}
+		// Leaves without data can leave out the fields that would be empty
+		self.build(name, data.unwrap_or(None), children.unwrap_or_default())
+	}
+}
+
+enum Field {
+	Name,
+	Data,
+	Children,
+}
+
+impl<'de> Deserialize<'de> for Field {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_identifier(FieldVisitor)
+	}
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+	type Value = Field;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "name, data or children")
+	}
+
+	fn visit_str<E: Error>(self, field: &str) -> Result<Field, E> {
+		match field {
+			"name" => Ok(Field::Name),
+			"data" => Ok(Field::Data),
+			"children" => Ok(Field::Children),
+			_ => Err(E::unknown_field(field, NODE_FIELDS)),
+		}
+	}
+}
+
+/// The store as a flat list of `(path, data)` pairs, for `#[serde(with = "filepath_tree::serde_flat")]`
+///
+/// There is one pair for each path `walk` returns, leaves without data are written with `None`,
+/// so the pairs are enough to rebuild every node. Paths are written as strings, names that are
+/// not valid UTF-8 go through `to_string_lossy` and come back with U+FFFD in place of the bytes
+/// that weren't. Only the paths and data are written, not settings like the base
+pub mod flat {
+	use crate::iter::Walker;
+	use crate::PathStore;
+
+	use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+	use serde::ser::{Serialize, SerializeSeq, Serializer};
+	use std::fmt;
+	use std::marker::PhantomData;
+	use std::path::PathBuf;
+
+	pub fn serialize<T: Serialize, S: Serializer>(store: &PathStore<T>, serializer: S) -> Result<S::Ok, S::Error> {
+		let len = store.count_nodes_where(|node| node.items.is_empty() || node.data.is_some());
+		let mut seq = serializer.serialize_seq(Some(len))?;
+		let mut walker = Walker::starting_at(store.root_path(), store.root.clone(), usize::MAX);
+		while let Some(result) = walker.next_with(|path, _, node| {
+			if node.items.is_empty() || node.data.is_some() {
+				Some(seq.serialize_element(&(path.to_string_lossy(), node.data.as_ref())))
+			} else {
+				None
+			}
+		}) {
+			result?;
+		}
+		seq.end()
+	}
+
+	/// Rebuilds the store by adding each pair with `add_path`, so `size` comes out the same
+	///
+	/// The store has the default settings, so a relative store can't be read back this way. A
+	/// path that `add_path` rejects is an error
+	pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<PathStore<T>, D::Error> {
+		deserializer.deserialize_seq(PairsVisitor(PhantomData))
+	}
+
+	struct PairsVisitor<T>(PhantomData<T>);
+
+	impl<'de, T: Deserialize<'de>> Visitor<'de> for PairsVisitor<T> {
+		type Value = PathStore<T>;
+
+		fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "a list of paths and their data")
+		}
+
+		fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PathStore<T>, A::Error> {
+			let mut store = PathStore::new(None);
+			while let Some((path, data)) = seq.next_element::<(PathBuf, Option<T>)>()? {
+				store.add_path(&path, data).map_err(A::Error::custom)?;
+			}
+			Ok(store)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::flat;
+	use crate::PathStore;
+
+	fn store() -> PathStore<i32> {
+		let mut store = PathStore::new(Some(0));
+		assert_eq!(store.add_path("/f/FDrive/files", Some(1)), Ok(true));
+		assert_eq!(store.add_path("/f/FDrive/hello", None), Ok(true));
+		assert_eq!(store.add_path("/f", Some(2)), Ok(false));
+		assert_eq!(store.add_path("/g", None), Ok(true));
+		store
+	}
+
+	#[test]
+	fn serde_round_trip() {
+		let store = store();
+		let json = serde_json::to_string(&store).unwrap();
+		assert!(json.starts_with(r#"{"data":0,"children":[{"name":"#), "{}", json);
+		let copy: PathStore<i32> = serde_json::from_str(&json).unwrap();
+		assert!(copy == store);
+		assert_eq!(copy.size(), store.size());
+		assert_eq!(copy.ancestors("/f/FDrive/files").unwrap().count(), 3);
+		assert_eq!(copy.count_under("/f"), 3);
+
+		let empty: PathStore<i32> = serde_json::from_str(&serde_json::to_string(&PathStore::<i32>::new(None)).unwrap()).unwrap();
+		assert!(empty.is_empty());
+
+		let name = serde_json::to_string(&std::ffi::OsString::from("a")).unwrap();
+		let node = |name: &str| format!(r#"{{"name":{},"data":null,"children":[]}}"#, name);
+		let twice = format!(r#"{{"data":null,"children":[{},{}]}}"#, node(&name), node(&name));
+		assert!(serde_json::from_str::<PathStore<i32>>(&twice).is_err());
+		let slash = serde_json::to_string(&std::ffi::OsString::from("a/b")).unwrap();
+		let invalid = format!(r#"{{"data":null,"children":[{}]}}"#, node(&slash));
+		assert!(serde_json::from_str::<PathStore<i32>>(&invalid).is_err());
+		// Leaves can leave out data and children
+		let short = format!(r#"{{"children":[{{"name":{}}}]}}"#, name);
+		assert_eq!(serde_json::from_str::<PathStore<i32>>(&short).unwrap().walk(), vec![std::path::PathBuf::from("/a")]);
+	}
+
+	#[test]
+	fn serde_depth_limit() {
+		let deep = |depth| {
+			let mut store = PathStore::new(None::<i32>);
+			assert_eq!(store.add_path(format!("/{}", vec!["a"; depth].join("/")), Some(1)), Ok(true));
+			store
+		};
+		let store = deep(super::MAX_DEPTH);
+		let copy: PathStore<i32> = serde_json::from_str(&serde_json::to_string(&store).unwrap()).unwrap();
+		assert!(copy == store);
+
+		let store = deep(super::MAX_DEPTH + 1);
+		let error = serde_json::to_string(&store).unwrap_err();
+		assert!(error.to_string().contains("deeper than the 62 levels"), "{}", error);
+		// The flat form doesn't nest
+		let mut json = Vec::new();
+		flat::serialize(&store, &mut serde_json::Serializer::new(&mut json)).unwrap();
+		let copy: PathStore<i32> = flat::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+		assert!(copy == store);
+	}
+
+	#[test]
+	fn serde_flat_round_trip() {
+		let store = store();
+		let mut json = Vec::new();
+		flat::serialize(&store, &mut serde_json::Serializer::new(&mut json)).unwrap();
+		assert_eq!(json, br#"[["/",0],["/f",2],["/f/FDrive/files",1],["/f/FDrive/hello",null],["/g",null]]"#);
+		let copy: PathStore<i32> = flat::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+		assert!(copy == store);
+		assert_eq!(copy.size(), store.size());
+
+		let relative = flat::deserialize::<i32, _>(&mut serde_json::Deserializer::from_str(r#"[["f",1]]"#));
+		assert!(relative.is_err());
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn serde_not_utf8() {
+		use std::ffi::OsStr;
+		use std::os::unix::ffi::OsStrExt;
+
+		let mut store = PathStore::new(None);
+		assert_eq!(store.add_path(OsStr::from_bytes(b"/f/not\xffutf8"), Some(1)), Ok(true));
+		let copy: PathStore<i32> = serde_json::from_str(&serde_json::to_string(&store).unwrap()).unwrap();
+		assert!(copy == store);
+
+		let mut json = Vec::new();
+		flat::serialize(&store, &mut serde_json::Serializer::new(&mut json)).unwrap();
+		let copy: PathStore<i32> = flat::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+		assert_eq!(copy.walk(), vec![std::path::PathBuf::from("/f/not\u{fffd}utf8")]);
+	}
+}
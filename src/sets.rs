@@ -0,0 +1,152 @@
+use crate::{PathNode, PathStore};
+
+use std::sync::{Arc, RwLock};
+
+impl<T: Clone> PathStore<T> {
+	/// Returns a new store with every path in either store
+	///
+	/// Same as merging a copy of other into a copy of this store, so like `merge` the data from
+	/// other wins where both have data
+	pub fn union(&self, other: &PathStore<T>) -> PathStore<T> {
+		let mut out = self.clone();
+		out.merge(other.clone());
+		out
+	}
+
+	/// Returns a new store with only the paths in both stores, with the data from this store
+	///
+	/// The root is in every store, so it always keeps its data
+	pub fn intersection(&self, other: &PathStore<T>) -> PathStore<T> {
+		let data = self.root.read().expect("Failed to lock tree node when copying tree").data.clone();
+		let mut out = self.empty_copy(data);
+		let mut stack = vec![(self.root.clone(), other.root.clone(), out.root.clone())];
+
+		while let Some((node, other_node, out_node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when copying tree");
+			let other_node = other_node.read().expect("Failed to lock tree node when copying tree");
+			let mut out_node_lock = out_node.write().unwrap();
+			for (name, child) in node.items.iter() {
+				if let Some((_, other_child)) = other_node.child(name, self.case_insensitive) {
+					let data = child.read().expect("Failed to lock tree node when copying tree").data.clone();
+					let copy = PathNode::with_parent(data, &out_node);
+					out_node_lock.insert_child(name.clone(), copy.clone(), self.case_insensitive);
+					out.size += 1;
+					stack.push((child.clone(), other_child.clone(), copy));
+				}
+			}
+		}
+		PathNode::recount(&out.root);
+		out
+	}
+
+	/// Returns a new store with only the paths in this store that are not in other
+	///
+	/// Parents needed to hold those paths are kept without data, and the root never has data
+	/// since it is in both stores
+	pub fn difference(&self, other: &PathStore<T>) -> PathStore<T> {
+		let mut out = self.empty_copy(None);
+		// Nodes in both stores, made only to hold what is under them, in the order they were made
+		let mut parents = Vec::new();
+		let mut stack = vec![(self.root.clone(), other.root.clone(), out.root.clone())];
+
+		while let Some((node, other_node, out_node)) = stack.pop() {
+			let node = node.read().expect("Failed to lock tree node when copying tree");
+			let other_node = other_node.read().expect("Failed to lock tree node when copying tree");
+			let mut out_node_lock = out_node.write().unwrap();
+			for (name, child) in node.items.iter() {
+				match other_node.child(name, self.case_insensitive) {
+					Some((_, other_child)) => {
+						let copy = PathNode::with_parent(None, &out_node);
+						out_node_lock.insert_child(name.clone(), copy.clone(), self.case_insensitive);
+						out.size += 1;
+						parents.push((out_node.clone(), name.clone(), copy.clone()));
+						stack.push((child.clone(), other_child.clone(), copy));
+					}
+					None => {
+						let child = child.read().expect("Failed to lock tree node when copying tree");
+						let copy = child.deep_clone();
+						copy.write().unwrap().parent = Some(Arc::downgrade(&out_node));
+						out_node_lock.insert_child(name.clone(), copy, self.case_insensitive);
+						out.size += 1 + child.descendants;
+					}
+				}
+			}
+		}
+
+		// Children were made after their parents, so going backwards empties the deepest first
+		for (parent, name, node) in parents.into_iter().rev() {
+			if node.read().expect("Failed to lock tree node when pruning tree").items.is_empty() {
+				parent.write().unwrap().remove_child(&name);
+				out.size -= 1;
+			}
+		}
+		PathNode::recount(&out.root);
+		out
+	}
+
+	/// An empty store with the same settings as this one
+	fn empty_copy(&self, data: Option<T>) -> PathStore<T> {
+		PathStore {
+			root: Arc::new(RwLock::new(PathNode::new(data))),
+			size: 0,
+			base: self.base.clone(),
+			case_insensitive: self.case_insensitive,
+			relative: self.relative,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::PathStore;
+	use std::path::PathBuf;
+
+	fn store(paths: &[(&str, Option<i32>)]) -> PathStore<i32> {
+		let mut store = PathStore::new(None);
+		for (path, data) in paths {
+			store.add_path(path, *data).unwrap();
+		}
+		store
+	}
+
+	fn assert_consistent(store: &PathStore<i32>) {
+		assert_eq!(store.size(), store.walk_all().len() - 1);
+		for path in store.walk_all().iter().skip(1) {
+			assert_eq!(store.ancestors(path).unwrap().count(), path.components().count() - 1);
+		}
+	}
+
+	#[test]
+	fn set_operations() {
+		let a = store(&[("/f/FDrive/files", Some(1)), ("/f/FDrive/hello", None), ("/g", Some(2))]);
+		let b = store(&[("/f/FDrive/files", Some(3)), ("/f/other", None), ("/g/x", Some(4))]);
+
+		let union = a.union(&b);
+		assert_eq!(union.walk_with_data(), vec![
+			(PathBuf::from("/f/FDrive/files"), 3),
+			(PathBuf::from("/g"), 2),
+			(PathBuf::from("/g/x"), 4),
+		]);
+		assert_eq!(union.walk().len(), 5);
+		assert_consistent(&union);
+		let mut merged = a.clone();
+		merged.merge(b.clone());
+		assert!(union == merged);
+
+		let intersection = a.intersection(&b);
+		assert!(intersection == store(&[("/f/FDrive/files", Some(1)), ("/g", Some(2))]));
+		assert_consistent(&intersection);
+
+		let difference = a.difference(&b);
+		assert_eq!(difference.walk(), vec![PathBuf::from("/f/FDrive/hello")]);
+		assert_eq!(difference.size(), 3);
+		assert_consistent(&difference);
+		assert!(b.difference(&a) == store(&[("/f/other", None), ("/g/x", Some(4))]));
+
+		// The originals are untouched
+		assert_eq!(a.size(), 5);
+		assert!(a.difference(&a).is_empty());
+		assert!(a.intersection(&a) == a);
+		assert!(a.union(&PathStore::new(None)) == a);
+	}
+}